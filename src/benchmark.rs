@@ -0,0 +1,143 @@
+use std::{fs, io, path::PathBuf};
+
+use log::warn;
+use ultraviolet::{Rotor3, Vec2, Vec3};
+
+const DEFAULT_DURATION: f32 = 30.0;
+const ORBIT_RADIUS: f32 = 15.0;
+const ORBIT_HEIGHT: f32 = 5.0;
+const ORBIT_PERIOD: f32 = 20.0;
+
+/// Nearest-rank percentile of already sorted `values`, 0 if there are none.
+fn percentile(sorted: &[f32], p: f32) -> f32 {
+    let index = ((p * sorted.len() as f32).ceil() as usize).saturating_sub(1);
+    sorted.get(index).copied().unwrap_or_default()
+}
+
+/// Runs a fixed camera path with no input, collecting frame statistics for a report.
+pub struct Benchmark {
+    pub duration: f32,
+    pub report_path: PathBuf,
+    frame_times: Vec<f32>,
+    draw_calls: Vec<usize>,
+    warmed_up: bool,
+}
+
+impl Benchmark {
+    /// Parses `--benchmark [seconds]` and `--benchmark-report <path>`, returning `None` if
+    /// benchmark mode wasn't requested.
+    pub fn from_args(args: impl Iterator<Item = String>) -> Option<Self> {
+        let mut args = args.peekable();
+        let mut benchmark = None;
+        let mut report_path = PathBuf::from("benchmark.json");
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--benchmark" => {
+                    benchmark = Some(
+                        args.next_if(|arg| arg.parse::<f32>().is_ok())
+                            .map_or(DEFAULT_DURATION, |arg| arg.parse().unwrap()),
+                    );
+                }
+                "--benchmark-report" => match args.next_if(|arg| !arg.starts_with("--")) {
+                    Some(path) => report_path = path.into(),
+                    None => warn!(
+                        "--benchmark-report requires a path, using {}",
+                        report_path.display()
+                    ),
+                },
+                _ => (),
+            }
+        }
+
+        benchmark.map(|duration| Self {
+            duration,
+            report_path,
+            frame_times: vec![],
+            draw_calls: vec![],
+            warmed_up: false,
+        })
+    }
+
+    /// Camera position and (yaw, pitch) at `time` seconds into the run, orbiting the origin.
+    pub fn camera(&self, time: f32) -> (Vec3, Vec2) {
+        let yaw = std::f32::consts::FRAC_PI_2 + std::f32::consts::TAU * time / ORBIT_PERIOD;
+
+        let forward = (-Vec3::unit_z()).rotated_by(Rotor3::from_rotation_xz(yaw).reversed());
+        let position = -forward * ORBIT_RADIUS + Vec3::unit_y() * ORBIT_HEIGHT;
+
+        (position, Vec2::new(yaw, std::f32::consts::FRAC_PI_8))
+    }
+
+    /// The first frame is discarded since its time includes startup and pipeline warm-up.
+    pub fn record_frame(&mut self, frame_time: f32, draw_calls: usize) {
+        if !self.warmed_up {
+            self.warmed_up = true;
+            return;
+        }
+
+        self.frame_times.push(frame_time);
+        self.draw_calls.push(draw_calls);
+    }
+
+    pub fn write_report(&self) -> io::Result<()> {
+        let mut frame_times = self.frame_times.clone();
+        frame_times.sort_by(f32::total_cmp);
+
+        let frames = frame_times.len();
+        let average = frame_times.iter().sum::<f32>() / frames.max(1) as f32 * 1000.0;
+
+        let report = format!(
+            concat!(
+                "{{\n",
+                "  \"duration_secs\": {},\n",
+                "  \"frames\": {},\n",
+                "  \"frame_time_ms\": {{\n",
+                "    \"avg\": {:.3},\n",
+                "    \"p95\": {:.3},\n",
+                "    \"p99\": {:.3},\n",
+                "    \"max\": {:.3}\n",
+                "  }},\n",
+                "  \"draw_calls_per_frame\": {{\n",
+                "    \"avg\": {:.1},\n",
+                "    \"max\": {}\n",
+                "  }}\n",
+                "}}\n"
+            ),
+            self.duration,
+            frames,
+            average,
+            percentile(&frame_times, 0.95) * 1000.0,
+            percentile(&frame_times, 0.99) * 1000.0,
+            percentile(&frame_times, 1.0) * 1000.0,
+            self.draw_calls.iter().sum::<usize>() as f32 / self.draw_calls.len().max(1) as f32,
+            self.draw_calls.iter().max().copied().unwrap_or_default(),
+        );
+
+        fs::write(&self.report_path, report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles() {
+        let values: Vec<_> = (1..=100).map(|i| i as f32).collect();
+
+        assert_eq!(percentile(&values, 0.5), 50.0);
+        assert_eq!(percentile(&values, 0.95), 95.0);
+        assert_eq!(percentile(&values, 0.99), 99.0);
+        assert_eq!(percentile(&values, 1.0), 100.0);
+        assert_eq!(percentile(&values, 0.0), 1.0);
+    }
+
+    #[test]
+    fn percentiles_of_few_values() {
+        assert_eq!(percentile(&[], 0.95), 0.0);
+        assert_eq!(percentile(&[4.0], 0.95), 4.0);
+        assert_eq!(percentile(&[1.0, 2.0, 3.0], 0.5), 2.0);
+        assert_eq!(percentile(&[1.0, 2.0, 3.0], 0.99), 3.0);
+    }
+}
@@ -3,7 +3,11 @@ use std::{marker::PhantomData, ops::Deref, rc::Rc};
 use ash::vk;
 use log::info;
 
-use crate::command_buffer::ActiveCommandBuffer;
+use crate::{
+    command_buffer::ActiveCommandBuffer,
+    memory_stats::{self, AllocationKind},
+    resource_tracker::{self, ResourceKind},
+};
 
 pub struct Buffer<T: Copy> {
     pub buffer: vk::Buffer,
@@ -112,6 +116,7 @@ fn copy_buffer<C: ActiveCommandBuffer, T: Copy + 'static>(
     }];
 
     unsafe { device.cmd_copy_buffer(**cmd_buf, **buffer, new_buffer, &copy_region) };
+    cmd_buf.mark_used(ResourceKind::Buffer, buffer.buffer);
     cmd_buf.add_dependency(buffer.clone());
 
     Buffer {
@@ -165,20 +170,30 @@ impl<T: Copy + 'static> Buffer<T> {
             .sharing_mode(vk::SharingMode::EXCLUSIVE);
 
         let buffer = unsafe { device.create_buffer(&buffer_info, None).unwrap() };
+        resource_tracker::track(ResourceKind::Buffer, buffer);
         let memory_requirements = unsafe { device.get_buffer_memory_requirements(buffer) };
 
+        let memory_type_index = find_memory_type(
+            instance,
+            physical_device,
+            memory_requirements.memory_type_bits,
+            properties,
+        );
+
         let alloc_info = vk::MemoryAllocateInfo::default()
             .allocation_size(memory_requirements.size)
-            .memory_type_index(find_memory_type(
-                instance,
-                physical_device,
-                memory_requirements.memory_type_bits,
-                properties,
-            ));
+            .memory_type_index(memory_type_index);
 
         let memory = unsafe { device.allocate_memory(&alloc_info, None).unwrap() };
         unsafe { device.bind_buffer_memory(buffer, memory, 0).unwrap() }
 
+        memory_stats::record_allocation(
+            memory,
+            AllocationKind::Buffer,
+            memory_type_index,
+            memory_requirements.size,
+        );
+
         (buffer, memory)
     }
 
@@ -240,5 +255,7 @@ impl<T: Copy> Drop for Buffer<T> {
             self.device.destroy_buffer(self.buffer, None);
             self.device.free_memory(self.memory, None);
         };
+        memory_stats::record_free(self.memory);
+        resource_tracker::untrack(ResourceKind::Buffer, self.buffer);
     }
 }
@@ -1,12 +1,23 @@
 use std::{ops::Deref, rc::Rc};
 
-use ash::vk;
+use ash::vk::{self, Handle};
 use log::info;
 
-use crate::device::Device;
+use crate::{
+    device::Device,
+    resource_tracker::{self, ResourceKind},
+};
 
 pub trait ActiveCommandBuffer: Deref<Target = vk::CommandBuffer> {
     fn add_dependency(&mut self, dependency: Rc<dyn std::any::Any + 'static>);
+
+    /// Marks a resource as read by this command buffer, for the debug build resource tracker.
+    fn mark_used<H: Handle>(&self, kind: ResourceKind, handle: H)
+    where
+        Self: Sized,
+    {
+        resource_tracker::mark_used(kind, handle);
+    }
 }
 
 pub struct OneTimeSubmitCommandBuffer {
@@ -4,8 +4,7 @@ use std::{
 };
 
 use ash::{ext, vk};
-use colored::Colorize;
-use log::info;
+use log::{debug, error, info, warn};
 
 pub const ENABLE_VALIDATION_LAYERS: bool = cfg!(debug_assertions);
 
@@ -38,16 +37,12 @@ impl DebugMessenger {
 
         let msg = format!("{message_type:?} [{message_id_name} ({message_id_number})]: {message}");
 
-        println!(
-            "{}\n",
-            match message_severity {
-                vk::DebugUtilsMessageSeverityFlagsEXT::INFO => msg.white().dimmed(),
-                vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE => msg.white(),
-                vk::DebugUtilsMessageSeverityFlagsEXT::WARNING => msg.yellow(),
-                vk::DebugUtilsMessageSeverityFlagsEXT::ERROR => msg.red(),
-                _ => msg.into(),
-            }
-        );
+        match message_severity {
+            vk::DebugUtilsMessageSeverityFlagsEXT::ERROR => error!("{msg}"),
+            vk::DebugUtilsMessageSeverityFlagsEXT::WARNING => warn!("{msg}"),
+            vk::DebugUtilsMessageSeverityFlagsEXT::INFO => info!("{msg}"),
+            _ => debug!("{msg}"),
+        }
 
         vk::FALSE
     }
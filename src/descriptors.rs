@@ -1,13 +1,13 @@
 use std::rc::Rc;
 
 use ash::vk;
-use log::info;
-
-use crate::renderer::MAX_FRAMES_IN_FLIGHT;
+use log::{debug, info};
 
 #[derive(Clone)]
 pub struct DescriptorSetLayout {
     pub layout: vk::DescriptorSetLayout,
+    /// Descriptors of each type needed by a single set with this layout.
+    pub pool_sizes: Vec<vk::DescriptorPoolSize>,
     device: Rc<ash::Device>,
 }
 
@@ -26,6 +26,21 @@ impl DescriptorSetLayout {
                 .stage_flags(vk::ShaderStageFlags::FRAGMENT),
         ];
 
+        let mut pool_sizes: Vec<vk::DescriptorPoolSize> = vec![];
+        for binding in &bindings {
+            match pool_sizes
+                .iter_mut()
+                .find(|size| size.ty == binding.descriptor_type)
+            {
+                Some(size) => size.descriptor_count += binding.descriptor_count,
+                None => pool_sizes.push(
+                    vk::DescriptorPoolSize::default()
+                        .ty(binding.descriptor_type)
+                        .descriptor_count(binding.descriptor_count),
+                ),
+            }
+        }
+
         let layout_info = vk::DescriptorSetLayoutCreateInfo::default().bindings(&bindings);
 
         let layout = unsafe {
@@ -34,7 +49,11 @@ impl DescriptorSetLayout {
                 .unwrap()
         };
 
-        Self { layout, device }
+        Self {
+            layout,
+            pool_sizes,
+            device,
+        }
     }
 }
 
@@ -46,44 +65,98 @@ impl Drop for DescriptorSetLayout {
     }
 }
 
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DescriptorPoolStats {
+    pub pools: usize,
+    pub capacity: u32,
+    pub allocated: u32,
+}
+
+/// Allocates descriptor sets for one layout, chaining on a new, larger pool whenever the
+/// current one runs out.
 pub struct DescriptorPool {
-    pub pool: vk::DescriptorPool,
+    pools: Vec<vk::DescriptorPool>,
+    layout: vk::DescriptorSetLayout,
+    pool_sizes: Vec<vk::DescriptorPoolSize>,
+    next_pool_sets: u32,
+    stats: DescriptorPoolStats,
     device: Rc<ash::Device>,
 }
 
 impl DescriptorPool {
-    pub fn new(device: Rc<ash::Device>) -> Self {
-        let pool_sizes = [
-            vk::DescriptorPoolSize::default()
-                .ty(vk::DescriptorType::UNIFORM_BUFFER)
-                .descriptor_count(MAX_FRAMES_IN_FLIGHT.try_into().unwrap()),
-            vk::DescriptorPoolSize::default()
-                .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-                .descriptor_count(MAX_FRAMES_IN_FLIGHT.try_into().unwrap()),
-        ];
+    pub fn new(device: Rc<ash::Device>, layout: &DescriptorSetLayout, initial_sets: u32) -> Self {
+        let mut pool = Self {
+            pools: vec![],
+            layout: layout.layout,
+            pool_sizes: layout.pool_sizes.clone(),
+            next_pool_sets: initial_sets.max(1),
+            stats: DescriptorPoolStats::default(),
+            device,
+        };
+        pool.grow(0);
+
+        pool
+    }
+
+    /// Adds a pool with room for at least `min_sets` sets, doubling the size each time.
+    fn grow(&mut self, min_sets: u32) {
+        let max_sets = self.next_pool_sets.max(min_sets);
+
+        let pool_sizes: Vec<_> = self
+            .pool_sizes
+            .iter()
+            .map(|size| size.descriptor_count(size.descriptor_count * max_sets))
+            .collect();
 
         let pool_info = vk::DescriptorPoolCreateInfo::default()
             .pool_sizes(&pool_sizes)
-            .max_sets(MAX_FRAMES_IN_FLIGHT.try_into().unwrap());
+            .max_sets(max_sets);
 
-        let pool = unsafe { device.create_descriptor_pool(&pool_info, None).unwrap() };
+        let pool = unsafe {
+            self.device
+                .create_descriptor_pool(&pool_info, None)
+                .unwrap()
+        };
+
+        self.pools.push(pool);
+        self.next_pool_sets = max_sets * 2;
+        self.stats.pools += 1;
+        self.stats.capacity += max_sets;
 
-        Self { pool, device }
+        debug!(
+            "descriptor pool {} created with room for {max_sets} sets",
+            self.stats.pools
+        );
     }
 
-    pub fn create_descriptor_sets(
-        &self,
-        set_layouts: &[vk::DescriptorSetLayout],
-    ) -> Vec<vk::DescriptorSet> {
-        let allocate_info = vk::DescriptorSetAllocateInfo::default()
-            .descriptor_pool(self.pool)
-            .set_layouts(&set_layouts);
+    /// Allocates `count` sets with the layout the pool was created for.
+    pub fn create_descriptor_sets(&mut self, count: u32) -> Vec<vk::DescriptorSet> {
+        let set_layouts = vec![self.layout; count as usize];
 
-        unsafe {
-            self.device
-                .allocate_descriptor_sets(&allocate_info)
-                .unwrap()
-        }
+        let device = self.device.clone();
+        let allocate = |pool| {
+            let allocate_info = vk::DescriptorSetAllocateInfo::default()
+                .descriptor_pool(pool)
+                .set_layouts(&set_layouts);
+
+            unsafe { device.allocate_descriptor_sets(&allocate_info) }
+        };
+
+        let descriptor_sets = match allocate(*self.pools.last().unwrap()) {
+            Err(vk::Result::ERROR_OUT_OF_POOL_MEMORY | vk::Result::ERROR_FRAGMENTED_POOL) => {
+                self.grow(count);
+                allocate(*self.pools.last().unwrap()).unwrap()
+            }
+            result => result.unwrap(),
+        };
+
+        self.stats.allocated += count;
+
+        descriptor_sets
+    }
+
+    pub fn stats(&self) -> DescriptorPoolStats {
+        self.stats
     }
 }
 
@@ -91,6 +164,8 @@ impl Drop for DescriptorPool {
     fn drop(&mut self) {
         info!("dropped descriptor pool");
 
-        unsafe { self.device.destroy_descriptor_pool(self.pool, None) };
+        for pool in &self.pools {
+            unsafe { self.device.destroy_descriptor_pool(*pool, None) };
+        }
     }
 }
@@ -1,7 +1,7 @@
 use std::{iter::zip, mem::offset_of, ops::Deref, ptr::slice_from_raw_parts, rc::Rc};
 
-use ash::{khr, vk};
-use log::info;
+use ash::{ext, khr, vk};
+use log::{info, warn};
 
 use crate::{instance::Instance, surface::Surface};
 
@@ -15,8 +15,47 @@ pub struct Device {
     pub mssa_samples: vk::SampleCountFlags,
     pub graphics_queue: vk::Queue,
     pub present_queue: vk::Queue,
+    pub memory_budget_supported: bool,
+    /// Whether samplers may use anisotropic filtering, it's dropped in safe mode.
+    pub sampler_anisotropy: bool,
 }
 
+fn device_name(properties: &vk::PhysicalDeviceProperties) -> String {
+    properties
+        .device_name_as_c_str()
+        .map_or("<unknown>".into(), |name| name.to_string_lossy())
+        .into_owned()
+}
+
+/// Higher is better: discrete GPUs first, then by the size of their largest device local heap.
+fn score_physical_device(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+) -> (u32, vk::DeviceSize) {
+    let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+    let memory_properties =
+        unsafe { instance.get_physical_device_memory_properties(physical_device) };
+
+    let type_score = match properties.device_type {
+        vk::PhysicalDeviceType::DISCRETE_GPU => 3,
+        vk::PhysicalDeviceType::INTEGRATED_GPU => 2,
+        vk::PhysicalDeviceType::VIRTUAL_GPU => 1,
+        _ => 0,
+    };
+
+    let device_local_memory = memory_properties.memory_heaps
+        [..memory_properties.memory_heap_count as usize]
+        .iter()
+        .filter(|heap| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+        .map(|heap| heap.size)
+        .max()
+        .unwrap_or_default();
+
+    (type_score, device_local_memory)
+}
+
+/// Picks the best scoring suitable device, or the one matching `gpu` by index or by name if
+/// that's given and suitable.
 fn pick_physical_device(
     instance: &ash::Instance,
     surface: &Surface,
@@ -24,6 +63,7 @@ fn pick_physical_device(
     requested_features: &vk::PhysicalDeviceFeatures,
     requested_features12: &vk::PhysicalDeviceVulkan12Features,
     requested_features13: &vk::PhysicalDeviceVulkan13Features,
+    gpu: Option<&str>,
 ) -> Option<(vk::PhysicalDevice, (u32, u32), vk::SampleCountFlags)> {
     fn feature_subset(
         requested_features: &vk::PhysicalDeviceFeatures,
@@ -104,7 +144,7 @@ fn pick_physical_device(
         .any(|(&requested, &capability)| requested != 0 && capability == 0)
     }
 
-    physical_devices.iter().find_map(|physical_device| unsafe {
+    let is_suitable = |physical_device: &vk::PhysicalDevice| unsafe {
         let mut features13 = vk::PhysicalDeviceVulkan13Features::default();
         let mut features12 = vk::PhysicalDeviceVulkan12Features::default();
         let mut features = vk::PhysicalDeviceFeatures2::default()
@@ -152,54 +192,101 @@ fn pick_physical_device(
             vk::SampleCountFlags::TYPE_1
         };
 
-        info!("Multisampling level: {max_usable_sample_count:?}");
+        let queue_families = instance.get_physical_device_queue_family_properties(*physical_device);
+        let supports_present = |index: u32| {
+            surface
+                .loader
+                .get_physical_device_surface_support(*physical_device, index, **surface)
+                .unwrap_or(false)
+        };
 
-        let mut graphics_index = Option::<u32>::None;
-        let mut present_index = Option::<u32>::None;
+        let graphics_families: Vec<u32> = (0..queue_families.len() as u32)
+            .filter(|&index| {
+                queue_families[index as usize]
+                    .queue_flags
+                    .contains(vk::QueueFlags::GRAPHICS)
+            })
+            .collect();
 
-        instance
-            .get_physical_device_queue_family_properties(*physical_device)
+        // A family that can do both avoids sharing swapchain images between queues
+        let queue_indices = match graphics_families
             .iter()
-            .enumerate()
-            .find_map(|(index, info)| {
-                if graphics_index.is_none() && info.queue_flags.contains(vk::QueueFlags::GRAPHICS) {
-                    graphics_index = Some(index as u32);
-                }
-
-                if present_index.is_none()
-                    && surface
-                        .loader
-                        .get_physical_device_surface_support(
-                            *physical_device,
-                            index as u32,
-                            **surface,
-                        )
-                        .unwrap()
-                {
-                    present_index = Some(index as u32);
-                }
-
-                if let (Some(graphics_index), Some(present_index)) = (graphics_index, present_index)
-                {
-                    Some((
-                        *physical_device,
-                        (graphics_index, present_index),
-                        max_usable_sample_count,
-                    ))
-                } else {
-                    None
-                }
-            })
-    })
+            .find(|&&index| supports_present(index))
+        {
+            Some(&index) => Some((index, index)),
+            None => graphics_families.first().and_then(|&graphics_index| {
+                (0..queue_families.len() as u32)
+                    .find(|&index| supports_present(index))
+                    .map(|present_index| (graphics_index, present_index))
+            }),
+        };
+
+        queue_indices
+            .map(|queue_indices| (*physical_device, queue_indices, max_usable_sample_count))
+    };
+
+    let mut candidates = vec![];
+    for (index, physical_device) in physical_devices.iter().enumerate() {
+        let properties = unsafe { instance.get_physical_device_properties(*physical_device) };
+        let (_, device_local_memory) = score_physical_device(instance, *physical_device);
+        let candidate = is_suitable(physical_device);
+
+        info!(
+            "GPU {index}: {} ({:?}, {} MiB device local){}",
+            device_name(&properties),
+            properties.device_type,
+            device_local_memory / (1024 * 1024),
+            if candidate.is_some() {
+                ""
+            } else {
+                ", unsuitable"
+            },
+        );
+
+        if let Some(candidate) = candidate {
+            candidates.push((index, device_name(&properties), candidate));
+        }
+    }
+
+    if let Some(gpu) = gpu {
+        let requested = candidates
+            .iter()
+            .find(|(index, name, _)| match gpu.parse::<usize>() {
+                Ok(requested_index) => *index == requested_index,
+                Err(_) => name.to_lowercase().contains(&gpu.to_lowercase()),
+            });
+
+        match requested {
+            Some((_, _, candidate)) => return Some(*candidate),
+            None => warn!("no suitable GPU matches {gpu:?}, picking one automatically"),
+        }
+    }
+
+    candidates
+        .into_iter()
+        .max_by_key(|(_, _, (physical_device, _, _))| {
+            score_physical_device(instance, *physical_device)
+        })
+        .map(|(_, _, candidate)| candidate)
 }
 
 impl Device {
-    pub fn new(instance: &Instance, surface: &Surface) -> Self {
-        let features = vk::PhysicalDeviceFeatures::default().sampler_anisotropy(true);
+    /// In safe mode no optional features are required, so any device that can draw to the
+    /// surface is accepted, and multisampling is off.
+    pub fn new(
+        instance: &Instance,
+        surface: &Surface,
+        safe_mode: bool,
+        gpu: Option<&str>,
+    ) -> Result<Self, String> {
+        let sampler_anisotropy = !safe_mode;
+
+        let features = vk::PhysicalDeviceFeatures::default().sampler_anisotropy(sampler_anisotropy);
         let mut features12 = vk::PhysicalDeviceVulkan12Features::default();
         let mut features13 = vk::PhysicalDeviceVulkan13Features::default();
 
-        let physical_devices = unsafe { instance.enumerate_physical_devices() }.unwrap();
+        let physical_devices = unsafe { instance.enumerate_physical_devices() }
+            .map_err(|e| format!("couldn't enumerate GPUs: {e}"))?;
         let (physical_device, (graphics_index, present_index), mssa_samples) =
             pick_physical_device(
                 instance,
@@ -208,35 +295,69 @@ impl Device {
                 &features,
                 &features12,
                 &features13,
+                gpu,
             )
-            .expect("Couldn't find suitable device");
+            .ok_or("couldn't find a suitable GPU")?;
+
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        info!("Using GPU: {}", device_name(&properties));
+        info!("Multisampling level: {mssa_samples:?}");
+
+        let mssa_samples = if safe_mode {
+            info!("safe mode: multisampling and anisotropic filtering disabled");
+            vk::SampleCountFlags::TYPE_1
+        } else {
+            mssa_samples
+        };
 
         let device_memory_properties =
             unsafe { instance.get_physical_device_memory_properties(physical_device) };
 
-        let device_extension_names = [khr::swapchain::NAME.as_ptr()];
+        let memory_budget_supported =
+            unsafe { instance.enumerate_device_extension_properties(physical_device) }
+                .map_err(|e| format!("couldn't enumerate device extensions: {e}"))?
+                .iter()
+                .any(|extension| {
+                    extension.extension_name_as_c_str() == Ok(ext::memory_budget::NAME)
+                });
+
+        let mut device_extension_names = vec![khr::swapchain::NAME.as_ptr()];
+        if memory_budget_supported {
+            device_extension_names.push(ext::memory_budget::NAME.as_ptr());
+        }
 
         let priorities = [1.0];
 
-        let queue_info = vk::DeviceQueueCreateInfo::default()
+        let mut queue_infos = vec![vk::DeviceQueueCreateInfo::default()
             .queue_family_index(graphics_index)
-            .queue_priorities(&priorities);
+            .queue_priorities(&priorities)];
+
+        if present_index != graphics_index {
+            info!("separate graphics ({graphics_index}) and present ({present_index}) queues");
+
+            queue_infos.push(
+                vk::DeviceQueueCreateInfo::default()
+                    .queue_family_index(present_index)
+                    .queue_priorities(&priorities),
+            );
+        }
 
         let device_create_info = vk::DeviceCreateInfo::default()
-            .queue_create_infos(std::slice::from_ref(&queue_info))
+            .queue_create_infos(&queue_infos)
             .enabled_extension_names(&device_extension_names)
             .enabled_features(&features)
             .push_next(&mut features12)
             .push_next(&mut features13);
 
         let device = Rc::new(
-            unsafe { instance.create_device(physical_device, &device_create_info, None) }.unwrap(),
+            unsafe { instance.create_device(physical_device, &device_create_info, None) }
+                .map_err(|e| format!("couldn't create device: {e}"))?,
         );
 
         let graphics_queue = unsafe { device.get_device_queue(graphics_index, 0) };
         let present_queue = unsafe { device.get_device_queue(present_index, 0) };
 
-        Self {
+        Ok(Self {
             device,
             physical_device,
             device_memory_properties,
@@ -245,7 +366,9 @@ impl Device {
             mssa_samples,
             graphics_queue,
             present_queue,
-        }
+            memory_budget_supported,
+            sampler_anisotropy,
+        })
     }
 }
 
@@ -1,8 +1,26 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{debug, warn};
 use sdl2::keyboard::Keycode;
 use ultraviolet::Vec2;
 
+/// thread::sleep routinely overshoots by a millisecond or so, so the last stretch of a frame
+/// limit is spun instead.
+const SPIN_THRESHOLD: Duration = Duration::from_millis(2);
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+/// Frames taking longer than this are logged and reported by the watchdog.
+const LONG_FRAME_THRESHOLD: Duration = Duration::from_millis(250);
+
 pub struct EventLoop {
     pump: sdl2::EventPump,
+    min_frame_time: Option<Duration>,
 }
 
 #[derive(Debug, Default)]
@@ -16,6 +34,43 @@ pub struct Inputs {
     pub down: bool,
     pub quit: bool,
     pub recreate_swapchain: bool,
+    pub toggle_borderless: bool,
+    pub toggle_fullscreen: bool,
+    pub log_memory_stats: bool,
+    pub cycle_debug_view: bool,
+    pub toggle_hdr: bool,
+}
+
+/// Frame timings over the last [`STATS_INTERVAL`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrameStats {
+    pub frames: u32,
+    pub total: Duration,
+    pub min: Duration,
+    pub max: Duration,
+    pub long_frames: u32,
+}
+
+impl FrameStats {
+    /// `work_time` is the part of `frame_time` spent rendering and handling events, without
+    /// the frame rate limiter's sleep, and is what counts towards long frames.
+    fn record(&mut self, frame_time: Duration, work_time: Duration) {
+        self.min = match self.frames {
+            0 => frame_time,
+            _ => self.min.min(frame_time),
+        };
+        self.max = self.max.max(frame_time);
+        self.total += frame_time;
+        self.frames += 1;
+
+        if work_time > LONG_FRAME_THRESHOLD {
+            self.long_frames += 1;
+        }
+    }
+
+    pub fn average(&self) -> Duration {
+        self.total / self.frames.max(1)
+    }
 }
 
 impl Inputs {
@@ -40,12 +95,77 @@ impl Inputs {
     }
 }
 
+fn sleep_until(deadline: Instant) {
+    let now = Instant::now();
+    if deadline > now + SPIN_THRESHOLD {
+        thread::sleep(deadline - now - SPIN_THRESHOLD);
+    }
+
+    while Instant::now() < deadline {
+        std::hint::spin_loop();
+    }
+}
+
+/// Warns from a separate thread if the frame counter stops advancing, so a hung frame is
+/// reported while it is still hung rather than after it finishes. Time spent in the frame
+/// rate limiter, flagged by `limiting`, doesn't count.
+fn spawn_watchdog(
+    frame_counter: Arc<AtomicU64>,
+    running: Arc<AtomicBool>,
+    limiting: Arc<AtomicBool>,
+    threshold: Duration,
+) -> thread::JoinHandle<()> {
+    thread::Builder::new()
+        .name("frame watchdog".to_owned())
+        .spawn(move || {
+            let mut last_frame = frame_counter.load(Ordering::Relaxed);
+            let mut stalled_for = Duration::ZERO;
+
+            while running.load(Ordering::Relaxed) {
+                thread::park_timeout(threshold);
+
+                let frame = frame_counter.load(Ordering::Relaxed);
+                if frame == last_frame
+                    && running.load(Ordering::Relaxed)
+                    && !limiting.load(Ordering::Relaxed)
+                {
+                    stalled_for += threshold;
+                    warn!("frame {frame} has been running for over {stalled_for:?}");
+                } else {
+                    stalled_for = Duration::ZERO;
+                }
+
+                last_frame = frame;
+            }
+        })
+        .unwrap()
+}
+
 impl EventLoop {
     pub fn new(pump: sdl2::EventPump) -> Self {
-        EventLoop { pump }
+        EventLoop {
+            pump,
+            min_frame_time: None,
+        }
+    }
+
+    /// Caps the loop at `fps` frames per second, or runs uncapped with `None`. Anything that
+    /// isn't a finite, positive rate is ignored with a warning.
+    pub fn frame_rate_limit(mut self, fps: Option<f32>) -> Self {
+        self.min_frame_time = match fps {
+            Some(fps) if fps.is_finite() && fps > 0.0 => Some(Duration::from_secs_f32(fps.recip())),
+            Some(fps) => {
+                warn!("ignoring invalid frame rate limit {fps}, running uncapped");
+                None
+            }
+            None => None,
+        };
+
+        self
     }
 
-    pub fn run<F: FnMut(&mut Inputs), G: FnMut(sdl2::event::Event, &mut Inputs)>(
+    /// `render` is passed the frame timings from the last complete [`STATS_INTERVAL`].
+    pub fn run<F: FnMut(&mut Inputs, &FrameStats), G: FnMut(sdl2::event::Event, &mut Inputs)>(
         &mut self,
         mut render: F,
         mut process_event: G,
@@ -55,8 +175,24 @@ impl EventLoop {
             std::f32::consts::FRAC_PI_8,
         ));
 
+        let frame_counter = Arc::new(AtomicU64::new(0));
+        let running = Arc::new(AtomicBool::new(true));
+        let limiting = Arc::new(AtomicBool::new(false));
+        let watchdog = spawn_watchdog(
+            frame_counter.clone(),
+            running.clone(),
+            limiting.clone(),
+            LONG_FRAME_THRESHOLD,
+        );
+
+        let mut frame_stats = FrameStats::default();
+        let mut stats = FrameStats::default();
+        let mut stats_start = Instant::now();
+
         'quit: loop {
-            render(&mut inputs);
+            let frame_start = Instant::now();
+
+            render(&mut inputs, &frame_stats);
 
             while let Some(event) = self.pump.poll_event() {
                 process_event(event, &mut inputs);
@@ -65,6 +201,40 @@ impl EventLoop {
             if inputs.quit {
                 break 'quit;
             }
+
+            let work_time = frame_start.elapsed();
+            if work_time > LONG_FRAME_THRESHOLD {
+                warn!("long frame: {work_time:?}");
+            }
+
+            frame_counter.fetch_add(1, Ordering::Relaxed);
+
+            if let Some(min_frame_time) = self.min_frame_time {
+                limiting.store(true, Ordering::Relaxed);
+                sleep_until(frame_start + min_frame_time);
+                limiting.store(false, Ordering::Relaxed);
+            }
+
+            stats.record(frame_start.elapsed(), work_time);
+
+            if stats_start.elapsed() >= STATS_INTERVAL {
+                debug!(
+                    "{} frames, avg {:?}, min {:?}, max {:?}, {} long",
+                    stats.frames,
+                    stats.average(),
+                    stats.min,
+                    stats.max,
+                    stats.long_frames
+                );
+
+                frame_stats = stats;
+                stats = FrameStats::default();
+                stats_start = Instant::now();
+            }
         }
+
+        running.store(false, Ordering::Relaxed);
+        watchdog.thread().unpark();
+        watchdog.join().unwrap();
     }
 }
@@ -6,7 +6,9 @@ use log::info;
 use crate::{
     buffer::{find_memory_type, Buffer},
     command_buffer::ActiveCommandBuffer,
+    memory_stats::{self, AllocationKind},
     pipeline::Pipeline,
+    resource_tracker::{self, ResourceKind},
 };
 
 pub struct SwapchainImage {
@@ -88,6 +90,7 @@ fn copy_buffer_to_image<C: ActiveCommandBuffer>(
             vk::ImageLayout::TRANSFER_DST_OPTIMAL,
             &regions,
         );
+        cmd_buf.mark_used(ResourceKind::Buffer, buffer.buffer);
         cmd_buf.add_dependency(buffer);
     }
 }
@@ -236,20 +239,30 @@ impl Image {
 
         let (image, memory) = unsafe {
             let image = device.create_image(&create_info, None).unwrap();
+            resource_tracker::track(ResourceKind::Image, image);
             let memory_requirements = device.get_image_memory_requirements(image);
 
+            let memory_type_index = find_memory_type(
+                instance,
+                physical_device,
+                memory_requirements.memory_type_bits,
+                properties,
+            );
+
             let alloc_info = vk::MemoryAllocateInfo::default()
                 .allocation_size(memory_requirements.size)
-                .memory_type_index(find_memory_type(
-                    instance,
-                    physical_device,
-                    memory_requirements.memory_type_bits,
-                    properties,
-                ));
+                .memory_type_index(memory_type_index);
 
             let memory = device.allocate_memory(&alloc_info, None).unwrap();
             device.bind_image_memory(image, memory, 0).unwrap();
 
+            memory_stats::record_allocation(
+                memory,
+                AllocationKind::Image,
+                memory_type_index,
+                memory_requirements.size,
+            );
+
             (image, memory)
         };
 
@@ -273,6 +286,8 @@ impl Drop for Image {
             self.device.destroy_image(self.image, None);
             self.device.free_memory(self.memory, None)
         };
+        memory_stats::record_free(self.memory);
+        resource_tracker::untrack(ResourceKind::Image, self.image);
     }
 }
 
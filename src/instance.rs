@@ -43,6 +43,15 @@ impl Instance {
             extension_names.push(ext::debug_utils::NAME.as_ptr());
         }
 
+        // Needed for the HDR colour spaces to show up in the surface formats
+        let available_extensions =
+            unsafe { entry.enumerate_instance_extension_properties(None) }.unwrap();
+        if available_extensions.iter().any(|extension| {
+            extension.extension_name_as_c_str() == Ok(ext::swapchain_colorspace::NAME)
+        }) {
+            extension_names.push(ext::swapchain_colorspace::NAME.as_ptr());
+        }
+
         let app_info = vk::ApplicationInfo::default()
             .application_name(app_name)
             .application_version(vk::make_api_version(0, 1, 0, 0))
@@ -0,0 +1,107 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    time::SystemTime,
+};
+
+/// Number of log files kept around, including the one for the current run.
+const MAX_LOG_FILES: usize = 5;
+
+pub fn log_dir() -> PathBuf {
+    let base = if cfg!(windows) {
+        std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Logs"))
+    } else {
+        std::env::var_os("XDG_STATE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state"))
+            })
+    };
+
+    base.unwrap_or_else(std::env::temp_dir).join("bengine")
+}
+
+fn log_file_path(index: usize) -> PathBuf {
+    match index {
+        0 => log_dir().join("bengine.log"),
+        _ => log_dir().join(format!("bengine.{index}.log")),
+    }
+}
+
+/// Shifts bengine.log to bengine.1.log and so on, dropping the oldest.
+fn rotate_log_files() -> io::Result<fs::File> {
+    fs::create_dir_all(log_dir())?;
+
+    for index in (0..MAX_LOG_FILES - 1).rev() {
+        match fs::rename(log_file_path(index), log_file_path(index + 1)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => (),
+        }
+    }
+
+    fs::File::create(log_file_path(0))
+}
+
+struct Tee {
+    file: fs::File,
+}
+
+impl Write for Tee {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        io::stderr().write_all(buf)?;
+        self.file.write_all(buf)?;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()?;
+        self.file.flush()
+    }
+}
+
+/// Sets up env_logger to write timestamped lines both to stderr and to a log file in
+/// [`log_dir`], since GUI builds may have no console. Logs at info and above unless
+/// `RUST_LOG` says otherwise.
+pub fn init() {
+    let mut builder = env_logger::Builder::new();
+    builder
+        .filter_level(log::LevelFilter::Info)
+        .parse_default_env();
+
+    builder.format(|buf, record| {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        let secs = now.as_secs();
+
+        writeln!(
+            buf,
+            "[{:02}:{:02}:{:02}.{:03} {:<5} {}] {}",
+            secs / 3600 % 24,
+            secs / 60 % 60,
+            secs % 60,
+            now.subsec_millis(),
+            record.level(),
+            record.target(),
+            record.args()
+        )
+    });
+
+    let file_error = match rotate_log_files() {
+        Ok(file) => {
+            builder.target(env_logger::Target::Pipe(Box::new(Tee { file })));
+            None
+        }
+        Err(e) => Some(e),
+    };
+
+    builder.init();
+
+    if let Some(e) = file_error {
+        log::warn!("failed to open log file in {}: {e}", log_dir().display());
+    }
+}
@@ -1,3 +1,4 @@
+pub mod benchmark;
 pub mod buffer;
 pub mod command_buffer;
 pub mod debug_messenger;
@@ -6,40 +7,56 @@ pub mod device;
 pub mod event_loop;
 pub mod image;
 pub mod instance;
+pub mod logging;
+pub mod memory_stats;
 pub mod mesh;
+pub mod metrics;
 pub mod node;
+pub mod panic_handler;
 pub mod pipeline;
 pub mod render_pass;
 pub mod renderer;
+pub mod resource_tracker;
+pub mod safe_mode;
 pub mod sampler;
+pub mod settings;
 pub mod shader_module;
+pub mod sprite;
 pub mod surface;
 pub mod swapchain;
 pub mod synchronization;
 pub mod vertex;
+pub mod window_target;
 
-use std::{io::Cursor, mem::offset_of, ptr::addr_of};
+use std::{cell::RefCell, io::Cursor, mem::offset_of, ptr::addr_of, rc::Rc};
 
 use ash::vk;
-use command_buffer::ActiveMultipleSubmitCommandBuffer;
+use benchmark::Benchmark;
+use command_buffer::{ActiveCommandBuffer, ActiveMultipleSubmitCommandBuffer};
 
 use ::image::GenericImageView;
 use device::Device;
 use event_loop::EventLoop;
 use image::{Image, SwapchainImage};
-use mesh::Mesh;
+use mesh::{DynamicMesh, Mesh};
+use metrics::SessionMetrics;
 use node::{Node, Object};
 use pipeline::Pipeline;
-use renderer::{Renderer, UniformBufferObject, MAX_FRAMES_IN_FLIGHT};
+use renderer::{Renderer, UniformBufferObject};
+use resource_tracker::ResourceKind;
 use sampler::Sampler;
+use settings::Settings;
+use sprite::{Sprite, SpriteBatch, SpritePipeline};
 
 use ultraviolet::{Isometry3, Rotor3, Vec2, Vec3};
 
-use sdl2::event::Event;
+use log::{error, info, warn};
+use sdl2::{
+    event::Event,
+    keyboard::{Keycode, Mod},
+};
 use vertex::Vertex;
-
-const WIDTH: u32 = 800;
-const HEIGHT: u32 = 600;
+use window_target::WindowMode;
 
 #[repr(C, align(32))]
 pub struct VertexPushConstants {
@@ -58,8 +75,56 @@ pub struct PushConstants {
 }
 
 fn main() {
-    env_logger::init();
-    let mut gfx = Renderer::new(WIDTH, HEIGHT);
+    logging::init();
+    panic_handler::install();
+
+    let mut saved_settings = Settings::load();
+
+    // Command line flags only apply to this run, they aren't saved
+    let mut settings = saved_settings.clone();
+    settings.graphics.hdr |= std::env::args().any(|arg| arg == "--hdr");
+    settings.metrics |= std::env::args().any(|arg| arg == "--metrics");
+
+    if let Some(fps) = std::env::args()
+        .skip_while(|arg| arg != "--fps-limit")
+        .nth(1)
+    {
+        // Out of range rates are rejected by EventLoop::frame_rate_limit
+        match fps.parse() {
+            Ok(fps) => settings.frame_rate_limit = Some(fps),
+            Err(_) => warn!("ignoring invalid --fps-limit {fps:?}"),
+        }
+    }
+
+    let previous_startup_failed = safe_mode::begin_startup();
+    settings.graphics.safe_mode =
+        previous_startup_failed || std::env::args().any(|arg| arg == "--safe-mode");
+    settings.graphics.gpu = std::env::args().skip_while(|arg| arg != "--gpu").nth(1);
+
+    let (width, height) = settings.graphics.window_size;
+    let mut gfx = Renderer::new(width, height, &settings.graphics);
+
+    if let Some(e) = &gfx.safe_mode_fallback {
+        safe_mode::show_warning(
+            &gfx.main_window.window,
+            &format!("bengine couldn't start with the normal graphics settings ({e})"),
+        );
+    } else if previous_startup_failed {
+        warn!("previous startup didn't finish, running in safe mode");
+        safe_mode::show_warning(
+            &gfx.main_window.window,
+            "bengine didn't finish starting last time",
+        );
+    }
+
+    if settings.graphics.window_mode != WindowMode::Windowed {
+        if let Err(e) = gfx
+            .main_window
+            .set_window_mode(settings.graphics.window_mode)
+        {
+            warn!("failed to restore window mode: {e}");
+        }
+    }
 
     let (teapot, suzanne, texture) =
         gfx.command_pool
@@ -106,11 +171,12 @@ fn main() {
         &gfx.instance,
         gfx.device.device.clone(),
         &gfx.device.physical_device,
+        gfx.device.sampler_anisotropy,
     );
 
-    for i in 0..MAX_FRAMES_IN_FLIGHT {
+    for i in 0..gfx.frames_in_flight {
         let buffer_info = [vk::DescriptorBufferInfo::default()
-            .buffer(*gfx.uniform_buffers[i].buffer)
+            .buffer(*gfx.main_window.uniform_buffers[i].buffer)
             .offset(0)
             .range(size_of::<UniformBufferObject>().try_into().unwrap())];
 
@@ -121,14 +187,14 @@ fn main() {
 
         let descriptor_writes = [
             vk::WriteDescriptorSet::default()
-                .dst_set(gfx.descriptor_sets[i])
+                .dst_set(gfx.main_window.descriptor_sets[i])
                 .dst_binding(0)
                 .dst_array_element(0)
                 .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
                 .descriptor_count(1)
                 .buffer_info(&buffer_info),
             vk::WriteDescriptorSet::default()
-                .dst_set(gfx.descriptor_sets[i])
+                .dst_set(gfx.main_window.descriptor_sets[i])
                 .dst_binding(1)
                 .dst_array_element(0)
                 .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
@@ -137,13 +203,69 @@ fn main() {
         ];
 
         unsafe { gfx.device.update_descriptor_sets(&descriptor_writes, &[]) };
+
+        resource_tracker::mark_used(
+            ResourceKind::Buffer,
+            gfx.main_window.uniform_buffers[i].buffer.buffer,
+        );
+    }
+    resource_tracker::mark_used(ResourceKind::Image, texture.image);
+    resource_tracker::mark_used(ResourceKind::Sampler, texture_sampler.sampler);
+
+    let mut sprites = SpriteBatch::new(&gfx);
+    let wheel_sprite = sprites.add_texture(&mut gfx, &texture, &texture_sampler);
+
+    const RIBBON_SEGMENTS: usize = 32;
+
+    /// A strip waving under the scene, rebuilt every frame. Each side has its own vertices so
+    /// it's lit by its own normal.
+    fn ribbon_geometry(time_secs: f32) -> (Vec<Vertex>, Vec<u32>) {
+        const LENGTH: f32 = 12.0;
+        const HALF_WIDTH: f32 = 1.0;
+        const AMPLITUDE: f32 = 0.5;
+
+        let mut vertices = Vec::with_capacity(4 * (RIBBON_SEGMENTS + 1));
+        for side in [1.0, -1.0] {
+            for i in 0..=RIBBON_SEGMENTS {
+                let t = i as f32 / RIBBON_SEGMENTS as f32;
+                let x = (t - 0.5) * LENGTH;
+                let phase = x + 2.0 * time_secs;
+
+                let pos = Vec3::new(x, -3.0 + AMPLITUDE * phase.sin(), 0.0);
+                let normal = Vec3::new(-AMPLITUDE * phase.cos(), 1.0, 0.0).normalized() * side;
+
+                for (z, v) in [(-HALF_WIDTH, 0.0), (HALF_WIDTH, 1.0)] {
+                    vertices.push(Vertex {
+                        pos: pos + Vec3::new(0.0, 0.0, z),
+                        normal,
+                        tex_coord: Vec2::new(t, v),
+                    });
+                }
+            }
+        }
+
+        let back = 2 * (RIBBON_SEGMENTS as u32 + 1);
+        let mut indices = Vec::with_capacity(12 * RIBBON_SEGMENTS);
+        for i in 0..RIBBON_SEGMENTS as u32 {
+            let (a, b, c, d) = (2 * i, 2 * i + 1, 2 * i + 2, 2 * i + 3);
+
+            indices.extend_from_slice(&[a, b, c, c, b, d]);
+            indices.extend_from_slice(&[a, c, b, c, d, b].map(|index| index + back));
+        }
+
+        (vertices, indices)
     }
 
+    let ribbon = Rc::new(RefCell::new(DynamicMesh::new(
+        &gfx,
+        4 * (RIBBON_SEGMENTS + 1),
+        12 * RIBBON_SEGMENTS,
+    )));
+
     let mut root_node = Node::empty()
         .add_child(Node::empty().add_object(Object::Mesh(teapot.into())))
-        .add_child(Node::empty().add_child(Node::empty().add_object(Object::Mesh(suzanne.into()))));
-
-    let mut event_loop = EventLoop::new(gfx.sdl_context.event_pump().unwrap());
+        .add_child(Node::empty().add_child(Node::empty().add_object(Object::Mesh(suzanne.into()))))
+        .add_child(Node::empty().add_object(Object::DynamicMesh(ribbon.clone())));
 
     let mut camera_position = Vec3::new(15.0, 5.0, 0.0);
 
@@ -153,12 +275,27 @@ fn main() {
 
     gfx.sdl_context.mouse().set_relative_mouse_mode(true);
 
+    // Only the main window has a swapchain to recreate
+    let main_window_id = gfx.main_window.window.id();
+
+    let mut benchmark = Benchmark::from_args(std::env::args().skip(1));
+    let benchmarking = benchmark.is_some();
+
+    // A frame cap would hide the renderer's real frame times from the benchmark report
+    let mut event_loop = EventLoop::new(gfx.sdl_context.event_pump().unwrap())
+        .frame_rate_limit(settings.frame_rate_limit.filter(|_| !benchmarking));
+    let mut view_rotation: Option<Vec2> = None;
+
+    let mut session_metrics = settings.metrics.then(SessionMetrics::new);
+
     let start_time = std::time::Instant::now();
 
+    let mut started = false;
+
     let mut previous_time =
         std::time::Instant::now() - std::time::Duration::from_secs_f64(1.0 / 60.0);
     event_loop.run(
-        |inputs| {
+        |inputs, frame_stats| {
             // Delta time calculation
             let new_time = std::time::Instant::now();
             let dt = (new_time - previous_time).as_secs_f32();
@@ -166,7 +303,14 @@ fn main() {
 
             let time_secs = (new_time - start_time).as_secs_f32();
 
-            let camera_rotation = get_camera_rotor(inputs.camera_rotation);
+            let smoothed_rotation = settings.mouse.smooth(
+                view_rotation.unwrap_or(inputs.camera_rotation),
+                inputs.camera_rotation,
+                dt,
+            );
+            view_rotation = Some(smoothed_rotation);
+
+            let camera_rotation = get_camera_rotor(smoothed_rotation);
 
             root_node.children[0].transform = Isometry3::new(
                 Vec3::new(0.0, -1.0, 0.0),
@@ -183,6 +327,11 @@ fn main() {
                 Rotor3::from_rotation_xz(2.0 * time_secs),
             );
 
+            let (ribbon_vertices, ribbon_indices) = ribbon_geometry(time_secs);
+            ribbon
+                .borrow_mut()
+                .set_geometry(&ribbon_vertices, &ribbon_indices);
+
             const MOVEMENT_SPEED: f32 = 5.0;
             let camera_movement = if inputs.forward {
                 -Vec3::unit_z()
@@ -210,26 +359,175 @@ fn main() {
                 + camera_movement.rotated_by(camera_rotation.reversed()))
                 * (MOVEMENT_SPEED * dt);
 
-            let camera_transform = Isometry3::new(camera_position, camera_rotation);
+            let camera_transform = match &benchmark {
+                Some(benchmark) => {
+                    let (position, rotation) = benchmark.camera(time_secs);
+                    Isometry3::new(position, get_camera_rotor(rotation))
+                }
+                None => Isometry3::new(camera_position, camera_rotation),
+            };
+
+            if inputs.log_memory_stats {
+                memory_stats::log_report(&gfx.instance, &gfx.device);
+                info!(
+                    "descriptor pool: {:?}",
+                    gfx.main_window.descriptor_pool.stats()
+                );
+                info!("frame pacing: {frame_stats:?}");
+                inputs.log_memory_stats = false;
+            }
+
+            if inputs.cycle_debug_view {
+                let preferences = &mut gfx.main_window.preferences;
+                preferences.debug_view = preferences.debug_view.next();
+                info!("debug view: {:?}", preferences.debug_view);
+
+                inputs.cycle_debug_view = false;
+                inputs.recreate_swapchain = true;
+            }
+
+            if inputs.toggle_hdr {
+                let hdr = !gfx.main_window.preferences.hdr;
+                gfx.set_hdr(hdr);
+                saved_settings.graphics.hdr = hdr;
+
+                info!(
+                    "HDR {}, surface format: {:?}",
+                    if hdr { "on" } else { "off" },
+                    gfx.main_window.swapchain.surface_format
+                );
+
+                inputs.toggle_hdr = false;
+            }
+
+            if inputs.toggle_borderless || inputs.toggle_fullscreen {
+                if gfx.main_window.window_mode == WindowMode::Windowed {
+                    saved_settings.graphics.window_size = gfx.main_window.window.size();
+                }
+
+                let result = if inputs.toggle_borderless {
+                    gfx.main_window.toggle_borderless()
+                } else {
+                    gfx.main_window.toggle_fullscreen()
+                };
+
+                match result {
+                    Ok(()) => {
+                        // Saved straight away so the mode survives a crash or a killed process
+                        saved_settings.graphics.window_mode = gfx.main_window.window_mode;
+                        if let Err(e) = saved_settings.save() {
+                            warn!("failed to save settings: {e}");
+                        }
+                    }
+                    Err(e) => warn!("failed to change window mode: {e}"),
+                }
 
+                inputs.toggle_borderless = false;
+                inputs.toggle_fullscreen = false;
+                inputs.recreate_swapchain = true;
+            }
+
+            sprites.draw(
+                wheel_sprite,
+                Sprite {
+                    position: Vec3::new(0.0, 4.0, 0.0),
+                    size: Vec2::new(2.0, 2.0),
+                    ..Default::default()
+                },
+            );
+
+            let draw_calls = root_node
+                .breadth_first()
+                .into_iter()
+                .flat_map(|(_, node)| &node.objects)
+                .filter(|object| match object {
+                    Object::Mesh(_) => true,
+                    Object::DynamicMesh(mesh) => mesh.borrow().index_count() > 0,
+                })
+                .count()
+                + sprites.draw_calls(camera_transform);
+
+            let frame = gfx.main_window.current_frame;
             inputs.recreate_swapchain = gfx.draw(
-                |device, pipeline, command_buffer, descriptor_set, uniform_buffer, image| {
+                |device, swapchain, command_buffer, descriptor_set, uniform_buffer, image| {
                     record_command_buffer(
                         device,
-                        pipeline,
+                        &swapchain.pipeline,
+                        &swapchain.sprite_pipeline,
                         command_buffer,
                         &descriptor_set,
                         uniform_buffer,
                         image,
                         &root_node,
+                        &mut sprites,
+                        frame,
                         camera_transform,
                     )
                 },
                 inputs.recreate_swapchain,
             );
+
+            sprites.clear();
+
+            if !started {
+                safe_mode::finish_startup();
+                started = true;
+            }
+
+            if let Some(session_metrics) = &mut session_metrics {
+                session_metrics.record_frame(dt);
+            }
+
+            if let Some(benchmark) = &mut benchmark {
+                if time_secs < benchmark.duration {
+                    benchmark.record_frame(dt, draw_calls);
+                } else {
+                    match benchmark.write_report() {
+                        Ok(()) => info!(
+                            "wrote benchmark report to {}",
+                            benchmark.report_path.display()
+                        ),
+                        Err(e) => error!("failed to write benchmark report: {e}"),
+                    }
+
+                    inputs.quit = true;
+                }
+            }
         },
         |event, inputs| match event {
             Event::Quit { timestamp: _ } => inputs.quit = true,
+            Event::KeyDown {
+                keycode: Some(Keycode::ESCAPE),
+                ..
+            } if benchmarking => inputs.quit = true,
+            Event::KeyDown { .. } | Event::KeyUp { .. } | Event::MouseMotion { .. }
+                if benchmarking => {}
+            Event::KeyDown {
+                keycode: Some(Keycode::RETURN),
+                keymod,
+                repeat: false,
+                ..
+            } if keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) => inputs.toggle_borderless = true,
+            Event::KeyDown {
+                keycode: Some(Keycode::F11),
+                repeat: false,
+                ..
+            } => inputs.toggle_fullscreen = true,
+            Event::KeyDown {
+                keycode: Some(Keycode::F3),
+                repeat: false,
+                ..
+            } => inputs.log_memory_stats = true,
+            Event::KeyDown {
+                keycode: Some(Keycode::F4),
+                repeat: false,
+                ..
+            } => inputs.cycle_debug_view = true,
+            Event::KeyDown {
+                keycode: Some(Keycode::F5),
+                repeat: false,
+                ..
+            } => inputs.toggle_hdr = true,
             Event::KeyDown {
                 keycode: Some(key),
                 repeat: false,
@@ -250,11 +548,9 @@ fn main() {
                 xrel,
                 yrel,
             } => {
-                const SENSITIVITY: f32 = 0.005;
-
                 inputs.camera_rotation = {
-                    let mut rotation =
-                        inputs.camera_rotation + Vec2::new(xrel as f32, yrel as f32) * SENSITIVITY;
+                    let mut rotation = inputs.camera_rotation
+                        + settings.mouse.apply(Vec2::new(xrel as f32, yrel as f32));
 
                     rotation.y = rotation
                         .y
@@ -265,26 +561,58 @@ fn main() {
             }
             Event::Window {
                 timestamp: _,
-                window_id: _,
-                win_event: sdl2::event::WindowEvent::SizeChanged(_, _),
-            } => {
+                window_id,
+                win_event:
+                    sdl2::event::WindowEvent::SizeChanged(_, _)
+                    | sdl2::event::WindowEvent::DisplayChanged(_),
+            } if window_id == main_window_id => {
                 inputs.recreate_swapchain = true;
             }
             _ => (),
         },
     );
 
-    gfx.wait_idle();
+    saved_settings.graphics.window_mode = gfx.main_window.window_mode;
+    if gfx.main_window.window_mode == WindowMode::Windowed {
+        saved_settings.graphics.window_size = gfx.main_window.window.size();
+    }
+
+    match saved_settings.save() {
+        Ok(()) => info!("saved settings to {}", Settings::path().display()),
+        Err(e) => error!("failed to save settings: {e}"),
+    }
+
+    if let Some(session_metrics) = &session_metrics {
+        match session_metrics.write() {
+            Ok(path) => info!("wrote session metrics to {}", path.display()),
+            Err(e) => error!("failed to write session metrics: {e}"),
+        }
+    }
+
+    resource_tracker::report_unused();
+
+    // Everything holding GPU resources has to go before the report, or it shows up as a leak
+    drop(root_node);
+    drop(ribbon);
+    drop(sprites);
+    drop(texture_sampler);
+    drop(texture);
+    drop(gfx);
+
+    resource_tracker::report_leaks();
 }
 
 pub fn record_command_buffer(
     device: &Device,
     pipeline: &Pipeline,
+    sprite_pipeline: &SpritePipeline,
     command_buffer: ActiveMultipleSubmitCommandBuffer,
     descriptor_set: &vk::DescriptorSet,
     uniform_buffer: &mut [UniformBufferObject],
     image: &SwapchainImage,
     root_node: &Node,
+    sprites: &mut SpriteBatch,
+    frame: usize,
     camera_transform: Isometry3,
 ) -> ActiveMultipleSubmitCommandBuffer {
     let clear_color = [
@@ -397,6 +725,9 @@ pub fn record_command_buffer(
                     Object::Mesh(mesh) => {
                         let mesh = mesh.as_ref();
 
+                        command_buffer.mark_used(ResourceKind::Buffer, mesh.vertex_buffer.buffer);
+                        command_buffer.mark_used(ResourceKind::Buffer, mesh.index_buffer.buffer);
+
                         let vertex_buffers = [mesh.vertex_buffer.buffer];
                         let offsets = [vk::DeviceSize::from(0u64)];
 
@@ -417,10 +748,13 @@ pub fn record_command_buffer(
                             0,
                         );
                     }
+                    Object::DynamicMesh(mesh) => mesh.borrow_mut().record(device, cmd_buf, frame),
                 }
             }
         }
 
+        sprites.record(device, sprite_pipeline, cmd_buf, frame, camera_transform);
+
         device.cmd_end_render_pass(cmd_buf);
     }
 
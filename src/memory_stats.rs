@@ -0,0 +1,136 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+};
+
+use ash::vk;
+use log::info;
+
+use crate::device::Device;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AllocationKind {
+    Buffer,
+    Image,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Allocation {
+    kind: AllocationKind,
+    memory_type_index: u32,
+    size: vk::DeviceSize,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AllocationTotals {
+    pub count: usize,
+    pub bytes: vk::DeviceSize,
+}
+
+impl AllocationTotals {
+    fn add(&mut self, size: vk::DeviceSize) {
+        self.count += 1;
+        self.bytes += size;
+    }
+}
+
+/// Live device memory allocations made through [`Buffer`](crate::buffer::Buffer) and
+/// [`Image`](crate::image::Image), keyed by memory handle.
+static ALLOCATIONS: LazyLock<Mutex<HashMap<vk::DeviceMemory, Allocation>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+pub fn record_allocation(
+    memory: vk::DeviceMemory,
+    kind: AllocationKind,
+    memory_type_index: u32,
+    size: vk::DeviceSize,
+) {
+    ALLOCATIONS.lock().unwrap().insert(
+        memory,
+        Allocation {
+            kind,
+            memory_type_index,
+            size,
+        },
+    );
+}
+
+pub fn record_free(memory: vk::DeviceMemory) {
+    ALLOCATIONS.lock().unwrap().remove(&memory);
+}
+
+pub fn totals(kind: AllocationKind) -> AllocationTotals {
+    ALLOCATIONS
+        .lock()
+        .unwrap()
+        .values()
+        .filter(|allocation| allocation.kind == kind)
+        .fold(AllocationTotals::default(), |mut totals, allocation| {
+            totals.add(allocation.size);
+            totals
+        })
+}
+
+fn format_bytes(bytes: vk::DeviceSize) -> String {
+    format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+/// Logs allocation totals and a per-heap breakdown, including the driver's budget for each
+/// heap when VK_EXT_memory_budget is available.
+pub fn log_report(instance: &ash::Instance, device: &Device) {
+    let mut budget = vk::PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+    let memory_properties = if device.memory_budget_supported {
+        let mut properties = vk::PhysicalDeviceMemoryProperties2::default().push_next(&mut budget);
+        unsafe {
+            instance.get_physical_device_memory_properties2(device.physical_device, &mut properties)
+        };
+
+        properties.memory_properties
+    } else {
+        device.device_memory_properties
+    };
+
+    let buffers = totals(AllocationKind::Buffer);
+    let images = totals(AllocationKind::Image);
+    info!(
+        "GPU memory: {} buffers ({}), {} images ({})",
+        buffers.count,
+        format_bytes(buffers.bytes),
+        images.count,
+        format_bytes(images.bytes)
+    );
+
+    let mut per_heap =
+        vec![AllocationTotals::default(); memory_properties.memory_heap_count as usize];
+    for allocation in ALLOCATIONS.lock().unwrap().values() {
+        let heap_index = memory_properties.memory_types[allocation.memory_type_index as usize]
+            .heap_index as usize;
+        per_heap[heap_index].add(allocation.size);
+    }
+
+    for (index, (heap, totals)) in memory_properties
+        .memory_heaps
+        .iter()
+        .zip(&per_heap)
+        .enumerate()
+    {
+        let device_local = heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL);
+        let budget = if device.memory_budget_supported {
+            format!(
+                ", process usage {} of {} budget",
+                format_bytes(budget.heap_usage[index]),
+                format_bytes(budget.heap_budget[index])
+            )
+        } else {
+            String::new()
+        };
+
+        info!(
+            "  heap {index}{}: {} allocations, {} of {}{budget}",
+            if device_local { " (device local)" } else { "" },
+            totals.count,
+            format_bytes(totals.bytes),
+            format_bytes(heap.size),
+        );
+    }
+}
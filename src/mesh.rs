@@ -2,9 +2,15 @@ use std::io::BufRead;
 
 use ash::vk;
 use obj::{load_obj, Obj};
+use ultraviolet::{Vec2, Vec3};
 
 use crate::{
-    buffer::Buffer, command_buffer::ActiveCommandBuffer, renderer::Renderer, vertex::Vertex,
+    buffer::{Buffer, MappedBuffer},
+    command_buffer::ActiveCommandBuffer,
+    device::Device,
+    renderer::Renderer,
+    resource_tracker::{self, ResourceKind},
+    vertex::Vertex,
 };
 
 #[derive(Debug)]
@@ -45,3 +51,134 @@ impl Mesh {
         }
     }
 }
+
+/// A copy of the geometry for one frame in flight.
+struct DynamicMeshFrame {
+    vertex_buffer: MappedBuffer<Vertex>,
+    index_buffer: MappedBuffer<u32>,
+    index_count: usize,
+    /// Whether this copy still needs the latest geometry written to it.
+    stale: bool,
+}
+
+/// A mesh whose geometry can be replaced from the CPU every frame, for things like trails and
+/// debug drawing. Each frame in flight has its own persistently mapped copy, which is only
+/// written from [`record`](Self::record) once that frame's fence has been waited on, so the
+/// GPU never reads a buffer while it's being written.
+pub struct DynamicMesh {
+    frames: Vec<DynamicMeshFrame>,
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+    max_vertices: usize,
+    max_indices: usize,
+}
+
+impl std::fmt::Debug for DynamicMesh {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DynamicMesh")
+            .field("vertices", &self.vertices.len())
+            .field("indices", &self.indices.len())
+            .field("max_vertices", &self.max_vertices)
+            .field("max_indices", &self.max_indices)
+            .finish()
+    }
+}
+
+impl DynamicMesh {
+    pub fn new(gfx: &Renderer, max_vertices: usize, max_indices: usize) -> Self {
+        let empty_vertex = Vertex {
+            pos: Vec3::zero(),
+            normal: Vec3::zero(),
+            tex_coord: Vec2::zero(),
+        };
+
+        // Vulkan doesn't allow zero sized buffers
+        let (buffer_vertices, buffer_indices) = (max_vertices.max(1), max_indices.max(1));
+
+        let frames = (0..gfx.frames_in_flight)
+            .map(|_| DynamicMeshFrame {
+                vertex_buffer: MappedBuffer::new(
+                    gfx.device.device.clone(),
+                    &gfx.instance,
+                    gfx.device.physical_device,
+                    &vec![empty_vertex; buffer_vertices],
+                    vk::BufferUsageFlags::VERTEX_BUFFER,
+                    vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+                ),
+                index_buffer: MappedBuffer::new(
+                    gfx.device.device.clone(),
+                    &gfx.instance,
+                    gfx.device.physical_device,
+                    &vec![0; buffer_indices],
+                    vk::BufferUsageFlags::INDEX_BUFFER,
+                    vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+                ),
+                index_count: 0,
+                stale: false,
+            })
+            .collect();
+
+        Self {
+            frames,
+            vertices: vec![],
+            indices: vec![],
+            max_vertices,
+            max_indices,
+        }
+    }
+
+    /// Replaces the geometry, which each frame in flight picks up the next time it's recorded.
+    pub fn set_geometry(&mut self, vertices: &[Vertex], indices: &[u32]) {
+        assert!(
+            vertices.len() <= self.max_vertices && indices.len() <= self.max_indices,
+            "dynamic mesh geometry exceeds its capacity"
+        );
+
+        self.vertices.clear();
+        self.vertices.extend_from_slice(vertices);
+        self.indices.clear();
+        self.indices.extend_from_slice(indices);
+
+        for frame in &mut self.frames {
+            frame.stale = true;
+        }
+    }
+
+    /// Indices in the latest geometry, no draw is recorded while this is 0.
+    pub fn index_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// Brings `frame`'s copy up to date and draws it. Must only be called while recording
+    /// `frame`'s command buffer, after its fence has been waited on.
+    pub fn record(&mut self, device: &Device, cmd_buf: vk::CommandBuffer, frame: usize) {
+        let frame = &mut self.frames[frame];
+
+        if frame.stale {
+            frame.vertex_buffer.mapped_memory[..self.vertices.len()]
+                .copy_from_slice(&self.vertices);
+            frame.index_buffer.mapped_memory[..self.indices.len()].copy_from_slice(&self.indices);
+            frame.index_count = self.indices.len();
+            frame.stale = false;
+        }
+
+        if frame.index_count == 0 {
+            return;
+        }
+
+        resource_tracker::mark_used(ResourceKind::Buffer, frame.vertex_buffer.buffer.buffer);
+        resource_tracker::mark_used(ResourceKind::Buffer, frame.index_buffer.buffer.buffer);
+
+        unsafe {
+            device.cmd_bind_vertex_buffers(cmd_buf, 0, &[*frame.vertex_buffer.buffer], &[0]);
+            device.cmd_bind_index_buffer(
+                cmd_buf,
+                *frame.index_buffer.buffer,
+                0,
+                vk::IndexType::UINT32,
+            );
+
+            device.cmd_draw_indexed(cmd_buf, frame.index_count.try_into().unwrap(), 1, 0, 0, 0);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use std::{
+    fs, io,
+    path::PathBuf,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::logging::log_dir;
+
+/// Upper bounds of the frame time histogram buckets, with a final bucket for anything slower.
+const FRAME_TIME_BUCKETS_MS: [f32; 5] = [8.3, 16.7, 33.3, 50.0, 100.0];
+
+/// Opt-in per-session statistics for playtests, only ever written to a local file.
+pub struct SessionMetrics {
+    started: SystemTime,
+    start: Instant,
+    frames: u64,
+    frame_time_histogram: [u64; FRAME_TIME_BUCKETS_MS.len() + 1],
+    longest_frame: f32,
+}
+
+impl Default for SessionMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SessionMetrics {
+    pub fn new() -> Self {
+        Self {
+            started: SystemTime::now(),
+            start: Instant::now(),
+            frames: 0,
+            frame_time_histogram: [0; FRAME_TIME_BUCKETS_MS.len() + 1],
+            longest_frame: 0.0,
+        }
+    }
+
+    pub fn record_frame(&mut self, frame_time: f32) {
+        let frame_time_ms = frame_time * 1000.0;
+        let bucket = FRAME_TIME_BUCKETS_MS
+            .iter()
+            .position(|&bound| frame_time_ms < bound)
+            .unwrap_or(FRAME_TIME_BUCKETS_MS.len());
+
+        self.frame_time_histogram[bucket] += 1;
+        self.frames += 1;
+        self.longest_frame = self.longest_frame.max(frame_time_ms);
+    }
+
+    /// Writes the session to `metrics/session-<unix time>.json` in the log directory.
+    pub fn write(&self) -> io::Result<PathBuf> {
+        let started = self
+            .started
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let histogram = self
+            .frame_time_histogram
+            .iter()
+            .enumerate()
+            .map(|(i, frames)| match FRAME_TIME_BUCKETS_MS.get(i) {
+                Some(bound) => format!("    {{ \"under_ms\": {bound}, \"frames\": {frames} }}"),
+                None => format!("    {{ \"under_ms\": null, \"frames\": {frames} }}"),
+            })
+            .collect::<Vec<_>>()
+            .join(",\n");
+
+        let report = format!(
+            concat!(
+                "{{\n",
+                "  \"started_unix_secs\": {},\n",
+                "  \"session_length_secs\": {:.1},\n",
+                "  \"frames\": {},\n",
+                "  \"longest_frame_ms\": {:.3},\n",
+                "  \"frame_time_histogram\": [\n",
+                "{}\n",
+                "  ]\n",
+                "}}\n"
+            ),
+            started,
+            self.start.elapsed().as_secs_f32(),
+            self.frames,
+            self.longest_frame,
+            histogram,
+        );
+
+        let dir = log_dir().join("metrics");
+        fs::create_dir_all(&dir)?;
+
+        let path = dir.join(format!("session-{started}.json"));
+        fs::write(&path, report)?;
+
+        Ok(path)
+    }
+}
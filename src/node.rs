@@ -1,14 +1,15 @@
-use std::{future::Future, rc::Rc};
+use std::{cell::RefCell, future::Future, rc::Rc};
 
 use genawaiter::{rc::gen, yield_};
 
-use crate::mesh::Mesh;
+use crate::mesh::{DynamicMesh, Mesh};
 
 use ultraviolet::Isometry3;
 
 #[derive(Clone, Debug)]
 pub enum Object {
     Mesh(Rc<Mesh>),
+    DynamicMesh(Rc<RefCell<DynamicMesh>>),
 }
 
 #[derive(Debug)]
@@ -0,0 +1,47 @@
+use std::{backtrace::Backtrace, fs, io::Write, path::PathBuf, time::SystemTime};
+
+use log::error;
+use sdl2::messagebox::{show_simple_message_box, MessageBoxFlag};
+
+use crate::logging::log_dir;
+
+pub fn crash_log_path() -> PathBuf {
+    log_dir().join("crash.log")
+}
+
+/// Writes panics and their backtraces to [`crash_log_path`] and tells the user where to find
+/// them, since on GUI builds there may be no console to print to.
+pub fn install() {
+    let default_hook = std::panic::take_hook();
+
+    std::panic::set_hook(Box::new(move |panic_info| {
+        default_hook(panic_info);
+
+        let backtrace = Backtrace::force_capture();
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        let report = format!("[{timestamp}] {panic_info}\n\nbacktrace:\n{backtrace}\n");
+
+        let path = crash_log_path();
+        let message = match fs::create_dir_all(log_dir())
+            .and_then(|()| fs::File::create(&path))
+            .and_then(|mut f| f.write_all(report.as_bytes()))
+        {
+            Ok(()) => format!(
+                "bengine has crashed.\n\n{panic_info}\n\nA crash log was written to {}",
+                path.display()
+            ),
+            Err(e) => {
+                error!("failed to write crash log to {}: {e}", path.display());
+                format!("bengine has crashed.\n\n{panic_info}")
+            }
+        };
+
+        if let Err(e) = show_simple_message_box(MessageBoxFlag::ERROR, "bengine", &message, None) {
+            error!("failed to show crash dialog: {e:?}");
+        }
+    }));
+}
@@ -9,6 +9,82 @@ use crate::{
     FragmentPushConstants, PushConstants, Vertex, VertexPushConstants,
 };
 
+/// Alternate outputs for the main pipeline, selected with a specialization constant.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u32)]
+pub enum DebugView {
+    #[default]
+    Lit,
+    Albedo,
+    Normals,
+    Depth,
+    /// Additive heatmap of how many fragments land on each pixel, with depth testing off.
+    Overdraw,
+}
+
+impl DebugView {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Lit => Self::Albedo,
+            Self::Albedo => Self::Normals,
+            Self::Normals => Self::Depth,
+            Self::Depth => Self::Overdraw,
+            Self::Overdraw => Self::Lit,
+        }
+    }
+}
+
+/// Projection parameters shared by every pipeline that draws from the camera's point of view,
+/// passed to the vertex shader as specialization constants 0 to 3.
+pub fn camera_parameters(extent: &vk::Extent2D) -> Vec4 {
+    let fov = 90f32.to_radians();
+
+    let ez = f32::tan(fov / 2.0).recip();
+    Vec4::new(
+        1.0,
+        1.0 * ((extent.width as f32) / (extent.height as f32)),
+        ez,
+        50.0,
+    )
+}
+
+pub const CAMERA_SPECIALIZATION_MAP_ENTRIES: [vk::SpecializationMapEntry; 4] = [
+    vk::SpecializationMapEntry {
+        constant_id: 0,
+        offset: offset_of!(Vec4, x) as u32,
+        size: std::mem::size_of::<f32>(),
+    },
+    vk::SpecializationMapEntry {
+        constant_id: 1,
+        offset: offset_of!(Vec4, y) as u32,
+        size: std::mem::size_of::<f32>(),
+    },
+    vk::SpecializationMapEntry {
+        constant_id: 2,
+        offset: offset_of!(Vec4, z) as u32,
+        size: std::mem::size_of::<f32>(),
+    },
+    vk::SpecializationMapEntry {
+        constant_id: 3,
+        offset: offset_of!(Vec4, w) as u32,
+        size: std::mem::size_of::<f32>(),
+    },
+];
+
+/// scRGB maps 1.0 to 80 nits, well below the brightness of SDR white on an HDR desktop.
+const SCRGB_WHITE_NITS: f32 = 80.0;
+/// Brightness SDR white is shown at on HDR output, BT.2408's reference white.
+const HDR_PAPER_WHITE_NITS: f32 = 203.0;
+
+/// Multiplier applied to the shaders' output colour, passed to the fragment shaders as
+/// specialization constant 5, so SDR white lands on paper white rather than at scRGB's 1.0.
+pub fn output_scale(color_space: vk::ColorSpaceKHR) -> f32 {
+    match color_space {
+        vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT => HDR_PAPER_WHITE_NITS / SCRGB_WHITE_NITS,
+        _ => 1.0,
+    }
+}
+
 pub struct Pipeline {
     pub pipeline: vk::Pipeline,
     pub pipeline_layout: vk::PipelineLayout,
@@ -24,49 +100,53 @@ impl Pipeline {
         extent: &vk::Extent2D,
         format: vk::Format,
         descriptor_set_layout: &DescriptorSetLayout,
+        debug_view: DebugView,
+        output_scale: f32,
     ) -> Self {
         let vert_shader_module = spv!(device.device.clone(), "shader.vert");
         let frag_shader_module = spv!(device.device.clone(), "shader.frag");
 
-        let fov = 90f32.to_radians();
+        let camera_parameters = camera_parameters(extent);
 
-        let ez = f32::tan(fov / 2.0).recip();
-        let camera_parameters = Vec4::new(
-            1.0,
-            1.0 * ((extent.width as f32) / (extent.height as f32)),
-            ez,
-            50.0,
-        );
+        let specialization_info = vk::SpecializationInfo::default()
+            .map_entries(&CAMERA_SPECIALIZATION_MAP_ENTRIES)
+            .data(unsafe {
+                std::slice::from_raw_parts(
+                    &camera_parameters as *const Vec4 as *const u8,
+                    std::mem::size_of::<Vec4>(),
+                )
+            });
 
-        let specialization_map_entries = [
-            vk::SpecializationMapEntry {
-                constant_id: 0,
-                offset: offset_of!(Vec4, x) as u32,
-                size: std::mem::size_of::<f32>(),
-            },
-            vk::SpecializationMapEntry {
-                constant_id: 1,
-                offset: offset_of!(Vec4, y) as u32,
-                size: std::mem::size_of::<f32>(),
-            },
+        #[repr(C)]
+        struct FragmentSpecialization {
+            debug_view: u32,
+            output_scale: f32,
+        }
+
+        let fragment_specialization = FragmentSpecialization {
+            debug_view: debug_view as u32,
+            output_scale,
+        };
+
+        let fragment_specialization_map_entries = [
             vk::SpecializationMapEntry {
-                constant_id: 2,
-                offset: offset_of!(Vec4, z) as u32,
-                size: std::mem::size_of::<f32>(),
+                constant_id: 4,
+                offset: offset_of!(FragmentSpecialization, debug_view) as u32,
+                size: std::mem::size_of::<u32>(),
             },
             vk::SpecializationMapEntry {
-                constant_id: 3,
-                offset: offset_of!(Vec4, w) as u32,
+                constant_id: 5,
+                offset: offset_of!(FragmentSpecialization, output_scale) as u32,
                 size: std::mem::size_of::<f32>(),
             },
         ];
 
-        let specialization_info = vk::SpecializationInfo::default()
-            .map_entries(&specialization_map_entries)
+        let fragment_specialization_info = vk::SpecializationInfo::default()
+            .map_entries(&fragment_specialization_map_entries)
             .data(unsafe {
                 std::slice::from_raw_parts(
-                    &camera_parameters as *const Vec4 as *const u8,
-                    std::mem::size_of::<Vec4>(),
+                    &fragment_specialization as *const FragmentSpecialization as *const u8,
+                    std::mem::size_of::<FragmentSpecialization>(),
                 )
             });
 
@@ -79,7 +159,8 @@ impl Pipeline {
             vk::PipelineShaderStageCreateInfo::default()
                 .stage(vk::ShaderStageFlags::FRAGMENT)
                 .module(*frag_shader_module)
-                .name(c"main"),
+                .name(c"main")
+                .specialization_info(&fragment_specialization_info),
         ];
 
         let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
@@ -130,17 +211,23 @@ impl Pipeline {
             .rasterization_samples(device.mssa_samples)
             .min_sample_shading(1.0);
 
+        let overdraw = debug_view == DebugView::Overdraw;
+
         let depth_stencil = vk::PipelineDepthStencilStateCreateInfo::default()
-            .depth_test_enable(true)
-            .depth_write_enable(true)
+            .depth_test_enable(!overdraw)
+            .depth_write_enable(!overdraw)
             .depth_compare_op(vk::CompareOp::LESS)
             .depth_bounds_test_enable(false)
             .stencil_test_enable(false);
 
         let color_blend_attachment = [vk::PipelineColorBlendAttachmentState {
-            blend_enable: vk::FALSE,
+            blend_enable: overdraw.into(),
             src_color_blend_factor: vk::BlendFactor::ONE,
-            dst_color_blend_factor: vk::BlendFactor::ZERO,
+            dst_color_blend_factor: if overdraw {
+                vk::BlendFactor::ONE
+            } else {
+                vk::BlendFactor::ZERO
+            },
             color_blend_op: vk::BlendOp::ADD,
             src_alpha_blend_factor: vk::BlendFactor::ONE,
             dst_alpha_blend_factor: vk::BlendFactor::ZERO,
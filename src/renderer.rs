@@ -1,57 +1,66 @@
 use ash::vk;
-use sdl2::sys::SDL_Vulkan_GetDrawableSize;
+use log::{error, warn};
 use ultraviolet::Isometry3;
 
 use crate::{
-    buffer::MappedBuffer,
-    command_buffer::{ActiveMultipleSubmitCommandBuffer, CommandPool, MultipleSubmitCommandBuffer},
+    command_buffer::{ActiveMultipleSubmitCommandBuffer, CommandPool},
     debug_messenger::{DebugMessenger, ENABLE_VALIDATION_LAYERS},
-    descriptors::{DescriptorPool, DescriptorSetLayout},
+    descriptors::DescriptorSetLayout,
     device::Device,
     image::SwapchainImage,
     instance::Instance,
-    pipeline::Pipeline,
+    settings::GraphicsSettings,
     surface::Surface,
-    swapchain::Swapchain,
-    synchronization::{Fence, Semaphore},
+    swapchain::{Swapchain, SwapchainPreferences},
+    window_target::WindowTarget,
 };
 
-pub const MAX_FRAMES_IN_FLIGHT: usize = 2;
+pub const MAX_FRAMES_IN_FLIGHT: usize = 3;
 
 #[derive(Clone, Copy, Debug, Default)]
 pub struct UniformBufferObject {
     pub view_transform: Isometry3,
 }
 
+fn create_window(
+    sdl_context: &sdl2::Sdl,
+    title: &str,
+    width: u32,
+    height: u32,
+) -> sdl2::video::Window {
+    sdl_context
+        .video()
+        .unwrap()
+        .window(title, width, height)
+        .allow_highdpi()
+        .vulkan()
+        .position_centered()
+        .resizable()
+        .build()
+        .map_err(|e| e.to_string())
+        .unwrap()
+}
+
 pub struct Renderer {
     // WARNING: Cleanup order matters here
-    pub image_avaliable_semaphores: Vec<Semaphore>,
-    pub render_finished_semaphores: Vec<Semaphore>,
-    pub in_flight_fences: Vec<Fence>,
+    pub main_window: WindowTarget,
 
     pub descriptor_set_layout: DescriptorSetLayout,
-    pub descriptor_pool: DescriptorPool,
-    pub descriptor_sets: Vec<vk::DescriptorSet>,
-    pub uniform_buffers: Vec<MappedBuffer<UniformBufferObject>>,
 
-    pub command_buffers: Vec<MultipleSubmitCommandBuffer>,
     pub command_pool: CommandPool,
 
-    pub swapchain: Swapchain,
-
     pub device: Device,
 
-    pub surface: Surface,
-
     pub debug_callback: Option<DebugMessenger>,
     pub instance: Instance,
 
     pub entry: ash::Entry,
 
-    pub window: sdl2::video::Window,
     pub sdl_context: sdl2::Sdl,
 
-    pub current_frame: usize,
+    pub frames_in_flight: usize,
+    /// Why startup fell back to safe mode, if the requested settings didn't work.
+    pub safe_mode_fallback: Option<String>,
 }
 
 impl Renderer {
@@ -62,7 +71,7 @@ impl Renderer {
     pub fn draw<
         F: FnMut(
             &Device,
-            &Pipeline,
+            &Swapchain,
             ActiveMultipleSubmitCommandBuffer,
             &vk::DescriptorSet,
             &mut [UniformBufferObject],
@@ -70,143 +79,44 @@ impl Renderer {
         ) -> ActiveMultipleSubmitCommandBuffer,
     >(
         &mut self,
-        mut record_command_buffer: F,
+        record_command_buffer: F,
         framebuffer_resized: bool,
     ) -> bool {
-        unsafe {
-            let fence = &[*self.in_flight_fences[self.current_frame]];
-            self.device.wait_for_fences(fence, true, u64::MAX).unwrap();
-
-            let (image_index, mut recreate_swapchain) = match (
-                self.swapchain.loader.acquire_next_image(
-                    *self.swapchain,
-                    u64::MAX,
-                    *self.image_avaliable_semaphores[self.current_frame],
-                    vk::Fence::null(),
-                ),
-                framebuffer_resized,
-            ) {
-                (Ok((image_index, true)), _) | (Ok((image_index, false)), true) => {
-                    (image_index, true)
-                }
-                (Ok((image_index, false)), false) => (image_index, false),
-                (Err(vk::Result::ERROR_OUT_OF_DATE_KHR), _) => {
-                    self.recreate_swapchain();
-                    return false;
-                }
-                (Err(_), _) => {
-                    panic!("failed to acquire swapchain image")
-                }
-            };
-
-            self.device.reset_fences(fence).unwrap();
-
-            take_mut::take(
-                self.command_buffers.get_mut(self.current_frame).unwrap(),
-                |command_buffer| {
-                    command_buffer
-                        .begin()
-                        .record(|command_buffer| {
-                            record_command_buffer(
-                                &self.device,
-                                &self.swapchain.pipeline,
-                                command_buffer,
-                                &self.descriptor_sets[self.current_frame],
-                                self.uniform_buffers[self.current_frame].mapped_memory,
-                                &self.swapchain.images[image_index as usize],
-                            )
-                        })
-                        .end()
-                        .submit(
-                            self.device.graphics_queue,
-                            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-                            *self.image_avaliable_semaphores[self.current_frame],
-                            *self.render_finished_semaphores[self.current_frame],
-                            *self.in_flight_fences[self.current_frame],
-                        )
-                },
-            );
-
-            let swapchains = [*self.swapchain];
-            let indices: [u32; 1] = [image_index];
-
-            let wait_semaphore = [*self.render_finished_semaphores[self.current_frame]];
-            let present_info = vk::PresentInfoKHR::default()
-                .wait_semaphores(&wait_semaphore)
-                .swapchains(&swapchains)
-                .image_indices(&indices);
-
-            match self
-                .swapchain
-                .loader
-                .queue_present(self.device.present_queue, &present_info)
-            {
-                Ok(true) | Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => recreate_swapchain = true,
-                Err(e) => panic!("{}", e),
-                _ => (),
-            };
-
-            if recreate_swapchain {
-                self.recreate_swapchain();
-            }
-        }
-
-        self.current_frame = (self.current_frame + 1) % MAX_FRAMES_IN_FLIGHT;
-
-        false
+        self.main_window.draw(
+            &self.instance,
+            &self.device,
+            &self.descriptor_set_layout,
+            record_command_buffer,
+            framebuffer_resized,
+        )
     }
 
     pub fn recreate_swapchain(&mut self) {
-        let mut width: std::ffi::c_int = 0;
-        let mut height: std::ffi::c_int = 0;
-
-        unsafe {
-            SDL_Vulkan_GetDrawableSize(
-                self.window.raw(),
-                (&mut width) as *mut std::ffi::c_int,
-                (&mut height) as *mut std::ffi::c_int,
-            )
-        };
-
-        let extent = vk::Extent2D {
-            width: width.try_into().unwrap(),
-            height: height.try_into().unwrap(),
-        };
-
-        self.wait_idle();
-
-        let swapchain = Swapchain::new(
+        self.main_window.recreate_swapchain(
             &self.instance,
             &self.device,
-            &self.surface.loader,
-            *self.surface,
-            extent,
             &self.descriptor_set_layout,
-            Some(&self.swapchain),
         );
+    }
 
-        self.swapchain = swapchain;
+    pub fn set_hdr(&mut self, enabled: bool) {
+        self.main_window.set_hdr(
+            &self.instance,
+            &self.device,
+            &self.descriptor_set_layout,
+            enabled,
+        );
     }
 
-    pub fn new(width: u32, height: u32) -> Self {
+    /// Starts with `settings`, and if the device or the main window's swapchain can't be
+    /// created that way, tries again in safe mode before giving up.
+    pub fn new(width: u32, height: u32, settings: &GraphicsSettings) -> Self {
         let entry = ash::Entry::linked();
 
         let sdl_context = sdl2::init().unwrap();
-        let window = {
-            sdl_context
-                .video()
-                .unwrap()
-                .window("bengine", width, height)
-                .allow_highdpi()
-                .vulkan()
-                .position_centered()
-                .resizable()
-                .build()
-                .map_err(|e| e.to_string())
-                .unwrap()
-        };
+        let mut window = Some(create_window(&sdl_context, "bengine", width, height));
 
-        let instance = Instance::new(&entry, &window);
+        let instance = Instance::new(&entry, window.as_ref().unwrap());
 
         let debug_callback = if ENABLE_VALIDATION_LAYERS {
             Some(DebugMessenger::new(&entry, &instance))
@@ -214,74 +124,108 @@ impl Renderer {
             None
         };
 
-        let surface = Surface::new(&entry, &window, &instance);
+        let mut start = |safe_mode: bool| {
+            // A failed attempt takes its window with it
+            let window = window
+                .take()
+                .unwrap_or_else(|| create_window(&sdl_context, "bengine", width, height));
 
-        let device = Device::new(&instance, &surface);
-
-        let descriptor_set_layout = DescriptorSetLayout::new(device.device.clone());
+            Graphics::new(&entry, &instance, window, settings, safe_mode)
+        };
 
-        let swapchain = Swapchain::new(
-            &instance,
-            &device,
-            &surface.loader,
-            *surface,
-            vk::Extent2D { width, height },
-            &descriptor_set_layout,
-            None,
-        );
+        let (graphics, safe_mode_fallback) = match start(settings.safe_mode) {
+            Ok(graphics) => (graphics, None),
+            Err(e) if !settings.safe_mode => {
+                error!("failed to start graphics: {e}, retrying in safe mode");
 
-        let command_pool = CommandPool::new(&device);
+                let graphics = start(true)
+                    .unwrap_or_else(|e| panic!("failed to start graphics, even in safe mode: {e}"));
 
-        let mut image_avaliable_semaphores = Vec::with_capacity(MAX_FRAMES_IN_FLIGHT);
-        let mut render_finished_semaphores = Vec::with_capacity(MAX_FRAMES_IN_FLIGHT);
-        let mut in_flight_fences = Vec::with_capacity(MAX_FRAMES_IN_FLIGHT);
+                (graphics, Some(e))
+            }
+            Err(e) => panic!("failed to start graphics in safe mode: {e}"),
+        };
 
-        let mut command_buffers = Vec::new();
-        let mut uniform_buffers = Vec::new();
+        Self {
+            main_window: graphics.main_window,
+            descriptor_set_layout: graphics.descriptor_set_layout,
+            command_pool: graphics.command_pool,
+            device: graphics.device,
+            debug_callback,
+            instance,
+            entry,
+            sdl_context,
+            frames_in_flight: graphics.frames_in_flight,
+            safe_mode_fallback,
+        }
+    }
+}
 
-        let descriptor_pool = DescriptorPool::new(device.device.clone());
+/// The parts of a [`Renderer`] that depend on the device, which startup may have to create
+/// twice.
+struct Graphics {
+    // WARNING: Cleanup order matters here, the same as in Renderer
+    main_window: WindowTarget,
+    descriptor_set_layout: DescriptorSetLayout,
+    command_pool: CommandPool,
+    device: Device,
+    frames_in_flight: usize,
+}
 
-        let descriptor_sets = {
-            let descriptor_set_layouts = [descriptor_set_layout.layout; MAX_FRAMES_IN_FLIGHT];
-            descriptor_pool.create_descriptor_sets(&descriptor_set_layouts)
+impl Graphics {
+    /// Safe mode drops MSAA, anisotropic filtering, HDR and low latency presentation, and
+    /// runs with a single frame in flight.
+    fn new(
+        entry: &ash::Entry,
+        instance: &Instance,
+        window: sdl2::video::Window,
+        settings: &GraphicsSettings,
+        safe_mode: bool,
+    ) -> Result<Self, String> {
+        let frames_in_flight = if safe_mode {
+            1
+        } else {
+            settings.frames_in_flight.clamp(1, MAX_FRAMES_IN_FLIGHT)
         };
 
-        for _ in 0..MAX_FRAMES_IN_FLIGHT {
-            command_buffers.push(command_pool.create_command_buffer());
-
-            image_avaliable_semaphores.push(Semaphore::new(device.device.clone()));
-            render_finished_semaphores.push(Semaphore::new(device.device.clone()));
-            in_flight_fences.push(Fence::new(device.device.clone()));
-
-            uniform_buffers.push(MappedBuffer::new(
-                device.device.clone(),
-                &instance,
-                device.physical_device,
-                &[UniformBufferObject::default()],
-                vk::BufferUsageFlags::UNIFORM_BUFFER,
-                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-            ));
-        }
+        let surface = Surface::new(entry, &window, instance);
 
-        Self {
-            image_avaliable_semaphores,
-            render_finished_semaphores,
-            in_flight_fences,
-            descriptor_pool,
+        let device = Device::new(instance, &surface, safe_mode, settings.gpu.as_deref())?;
+
+        let descriptor_set_layout = DescriptorSetLayout::new(device.device.clone());
+
+        let command_pool = CommandPool::new(&device);
+
+        let main_window = WindowTarget::new(
+            window,
+            surface,
+            instance,
+            &device,
+            &command_pool,
+            &descriptor_set_layout,
+            frames_in_flight,
+            SwapchainPreferences {
+                hdr: settings.hdr && !safe_mode,
+                low_latency: settings.low_latency && !safe_mode,
+                ..Default::default()
+            },
+        )?;
+
+        Ok(Self {
+            main_window,
             descriptor_set_layout,
-            descriptor_sets,
-            uniform_buffers,
-            command_buffers,
             command_pool,
-            swapchain,
             device,
-            surface,
-            debug_callback,
-            instance,
-            sdl_context,
-            window,
-            entry,
-            current_frame: 0,
+            frames_in_flight,
+        })
+    }
+}
+
+impl Drop for Renderer {
+    fn drop(&mut self) {
+        // Runs before any of the fields are destroyed, including while unwinding from a panic.
+        if let Err(e) = unsafe { self.device.device_wait_idle() } {
+            warn!("failed to wait for device idle before teardown: {e}");
         }
     }
 }
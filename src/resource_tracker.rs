@@ -0,0 +1,130 @@
+#[cfg(debug_assertions)]
+use std::{
+    backtrace::Backtrace,
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+use ash::vk::Handle;
+#[cfg(debug_assertions)]
+use log::{info, warn};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ResourceKind {
+    Buffer,
+    Image,
+    Sampler,
+}
+
+#[cfg(debug_assertions)]
+struct TrackedResource {
+    created: Instant,
+    /// Last time the resource was read by a command buffer or written into a descriptor set.
+    last_used: Option<Instant>,
+    backtrace: Backtrace,
+}
+
+/// Resources alive for this long without ever being used are reported as unused.
+#[cfg(debug_assertions)]
+const UNUSED_REPORT_AGE: Duration = Duration::from_secs(10);
+
+#[cfg(debug_assertions)]
+static LIVE_RESOURCES: LazyLock<Mutex<HashMap<(ResourceKind, u64), TrackedResource>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Registers a newly created resource. Only debug builds keep the registry, release builds
+/// compile this to nothing.
+pub fn track<H: Handle>(kind: ResourceKind, handle: H) {
+    #[cfg(debug_assertions)]
+    LIVE_RESOURCES.lock().unwrap().insert(
+        (kind, handle.as_raw()),
+        TrackedResource {
+            created: Instant::now(),
+            last_used: None,
+            backtrace: Backtrace::force_capture(),
+        },
+    );
+
+    #[cfg(not(debug_assertions))]
+    let _ = (kind, handle);
+}
+
+pub fn untrack<H: Handle>(kind: ResourceKind, handle: H) {
+    #[cfg(debug_assertions)]
+    LIVE_RESOURCES
+        .lock()
+        .unwrap()
+        .remove(&(kind, handle.as_raw()));
+
+    #[cfg(not(debug_assertions))]
+    let _ = (kind, handle);
+}
+
+/// Records that a resource is still in use, from wherever it's recorded into a command buffer
+/// or bound through a descriptor set.
+pub fn mark_used<H: Handle>(kind: ResourceKind, handle: H) {
+    #[cfg(debug_assertions)]
+    if let Some(resource) = LIVE_RESOURCES
+        .lock()
+        .unwrap()
+        .get_mut(&(kind, handle.as_raw()))
+    {
+        resource.last_used = Some(Instant::now());
+    }
+
+    #[cfg(not(debug_assertions))]
+    let _ = (kind, handle);
+}
+
+/// Logs every live resource that has existed for a while but never been used.
+pub fn report_unused() {
+    #[cfg(debug_assertions)]
+    {
+        let live_resources = LIVE_RESOURCES.lock().unwrap();
+        let unused: Vec<_> = live_resources
+            .iter()
+            .filter(|(_, resource)| {
+                resource.last_used.is_none() && resource.created.elapsed() >= UNUSED_REPORT_AGE
+            })
+            .collect();
+
+        for ((kind, handle), resource) in &unused {
+            warn!(
+                "{kind:?} {handle:#x} alive for {:?} but never used, created at:\n{}",
+                resource.created.elapsed(),
+                resource.backtrace
+            );
+        }
+
+        if !unused.is_empty() {
+            warn!("{} resources never used", unused.len());
+        }
+    }
+}
+
+/// Logs every resource that hasn't been destroyed, along with where it was created.
+pub fn report_leaks() {
+    #[cfg(debug_assertions)]
+    {
+        let live_resources = LIVE_RESOURCES.lock().unwrap();
+        if live_resources.is_empty() {
+            info!("no leaked resources");
+            return;
+        }
+
+        for ((kind, handle), resource) in live_resources.iter() {
+            let last_used = match resource.last_used {
+                Some(last_used) => format!("last used {:?} ago", last_used.elapsed()),
+                None => "never used".to_owned(),
+            };
+
+            warn!(
+                "leaked {kind:?} {handle:#x}, alive for {:?}, {last_used}, created at:\n{}",
+                resource.created.elapsed(),
+                resource.backtrace
+            );
+        }
+        warn!("{} resources leaked", live_resources.len());
+    }
+}
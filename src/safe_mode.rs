@@ -0,0 +1,48 @@
+use std::{fs, io, path::PathBuf};
+
+use log::{error, warn};
+use sdl2::messagebox::{show_simple_message_box, MessageBoxFlag};
+
+use crate::settings::config_dir;
+
+/// Exists from the start of a run until the first frame has been presented, so finding it at
+/// startup means the previous run never got that far. This only catches crashes, startup
+/// errors the renderer can see are retried in safe mode straight away by `Renderer::new`.
+fn startup_marker_path() -> PathBuf {
+    config_dir().join("startup.marker")
+}
+
+/// Marks startup as in progress, returning whether the previous startup failed.
+pub fn begin_startup() -> bool {
+    let path = startup_marker_path();
+    let previous_failed = path.exists();
+
+    if let Err(e) = fs::create_dir_all(config_dir()).and_then(|()| fs::write(&path, [])) {
+        warn!("failed to write {}: {e}", path.display());
+    }
+
+    previous_failed
+}
+
+pub fn finish_startup() {
+    let path = startup_marker_path();
+
+    match fs::remove_file(&path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            warn!("failed to remove {}: {e}", path.display())
+        }
+        _ => (),
+    }
+}
+
+/// Tells the player safe mode is on, `reason` being a sentence fragment saying why.
+pub fn show_warning(window: &sdl2::video::Window, reason: &str) {
+    let message = format!(
+        "{reason}, so it's running in safe mode with multisampling, anisotropic filtering and \
+         HDR off and one frame in flight.\n\nIt will try the normal settings again next time."
+    );
+
+    if let Err(e) = show_simple_message_box(MessageBoxFlag::WARNING, "bengine", &message, window) {
+        error!("failed to show safe mode dialog: {e:?}");
+    }
+}
@@ -3,6 +3,8 @@ use std::rc::Rc;
 use ash::vk;
 use log::info;
 
+use crate::resource_tracker::{self, ResourceKind};
+
 pub struct Sampler {
     pub sampler: vk::Sampler,
     device: Rc<ash::Device>,
@@ -13,6 +15,7 @@ impl Sampler {
         instance: &ash::Instance,
         device: Rc<ash::Device>,
         physical_device: &vk::PhysicalDevice,
+        anisotropy: bool,
     ) -> Self {
         let sampler_info = vk::SamplerCreateInfo::default()
             .mag_filter(vk::Filter::LINEAR)
@@ -20,7 +23,7 @@ impl Sampler {
             .address_mode_u(vk::SamplerAddressMode::REPEAT)
             .address_mode_v(vk::SamplerAddressMode::REPEAT)
             .address_mode_w(vk::SamplerAddressMode::REPEAT)
-            .anisotropy_enable(true)
+            .anisotropy_enable(anisotropy)
             .max_anisotropy(unsafe {
                 instance
                     .get_physical_device_properties(*physical_device)
@@ -37,6 +40,7 @@ impl Sampler {
             .max_lod(0.0);
 
         let sampler = unsafe { device.create_sampler(&sampler_info, None).unwrap() };
+        resource_tracker::track(ResourceKind::Sampler, sampler);
 
         Self { sampler, device }
     }
@@ -46,5 +50,6 @@ impl Drop for Sampler {
     fn drop(&mut self) {
         info!("dropped sampler");
         unsafe { self.device.destroy_sampler(self.sampler, None) };
+        resource_tracker::untrack(ResourceKind::Sampler, self.sampler);
     }
 }
@@ -0,0 +1,382 @@
+use std::{fs, io, path::PathBuf, str::FromStr};
+
+use log::{info, warn};
+use ultraviolet::Vec2;
+
+use crate::window_target::WindowMode;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MouseSettings {
+    /// Radians of camera rotation per pixel of mouse movement.
+    pub sensitivity: f32,
+    pub invert_y: bool,
+    /// Time constant in seconds for easing the camera towards the mouse, 0 disables smoothing.
+    pub smoothing: f32,
+    /// Extra sensitivity per pixel moved in a single event, 0 gives a linear response.
+    pub acceleration: f32,
+}
+
+impl Default for MouseSettings {
+    fn default() -> Self {
+        Self {
+            sensitivity: 0.005,
+            invert_y: false,
+            smoothing: 0.0,
+            acceleration: 0.0,
+        }
+    }
+}
+
+impl MouseSettings {
+    /// Converts a raw mouse delta in pixels into a change in camera rotation.
+    pub fn apply(&self, delta: Vec2) -> Vec2 {
+        let delta = if self.invert_y {
+            Vec2::new(delta.x, -delta.y)
+        } else {
+            delta
+        };
+
+        delta * self.sensitivity * (1.0 + self.acceleration * delta.mag())
+    }
+
+    /// Moves `current` towards `target`, frame rate independently.
+    pub fn smooth(&self, current: Vec2, target: Vec2, dt: f32) -> Vec2 {
+        if self.smoothing <= 0.0 {
+            return target;
+        }
+
+        current + (target - current) * (1.0 - f32::exp(-dt / self.smoothing))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GraphicsSettings {
+    /// Number of frames the CPU may record ahead of the GPU, between 1 and
+    /// [`MAX_FRAMES_IN_FLIGHT`](crate::renderer::MAX_FRAMES_IN_FLIGHT).
+    pub frames_in_flight: usize,
+    /// Present with MAILBOX where available instead of waiting on vsync.
+    pub low_latency: bool,
+    pub hdr: bool,
+    /// Size of the window when windowed, and the initial size before `window_mode` is applied.
+    pub window_size: (u32, u32),
+    pub window_mode: WindowMode,
+    /// Start with no MSAA, anisotropic filtering or HDR and one frame in flight. Set for a run
+    /// after one that crashed during startup, and never saved. The renderer also falls back to
+    /// it on its own if the normal settings fail.
+    pub safe_mode: bool,
+    /// Index or part of the name of the GPU to use, from `--gpu`. Picked automatically if
+    /// unset or if it doesn't match a suitable device.
+    pub gpu: Option<String>,
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        Self {
+            frames_in_flight: 2,
+            low_latency: false,
+            hdr: false,
+            window_size: (800, 600),
+            window_mode: WindowMode::Windowed,
+            safe_mode: false,
+            gpu: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Settings {
+    pub mouse: MouseSettings,
+    pub graphics: GraphicsSettings,
+    /// Frames per second to cap the main loop at, `None` for uncapped.
+    pub frame_rate_limit: Option<f32>,
+    /// Record local session metrics for playtests. Off unless the player opts in, and nothing
+    /// is ever sent anywhere.
+    pub metrics: bool,
+}
+
+pub fn config_dir() -> PathBuf {
+    let base = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+
+    base.unwrap_or_else(std::env::temp_dir).join("bengine")
+}
+
+fn parse<T: FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value {value:?}"))
+}
+
+fn format_window_mode(mode: WindowMode) -> String {
+    match mode {
+        WindowMode::Windowed => "windowed".to_owned(),
+        WindowMode::Borderless { display } => format!("borderless {display}"),
+        WindowMode::Fullscreen {
+            display,
+            width,
+            height,
+        } => format!("fullscreen {display} {width} {height}"),
+    }
+}
+
+fn parse_window_mode(value: &str) -> Result<WindowMode, String> {
+    let words: Vec<_> = value.split_whitespace().collect();
+
+    match words[..] {
+        ["windowed"] => Ok(WindowMode::Windowed),
+        ["borderless", display] => Ok(WindowMode::Borderless {
+            display: parse(display)?,
+        }),
+        ["fullscreen", display, width, height] => Ok(WindowMode::Fullscreen {
+            display: parse(display)?,
+            width: parse(width)?,
+            height: parse(height)?,
+        }),
+        _ => Err(format!("invalid window mode {value:?}")),
+    }
+}
+
+impl Settings {
+    pub fn path() -> PathBuf {
+        config_dir().join("settings.cfg")
+    }
+
+    /// Reads saved settings, falling back to defaults for a missing file and for any line that
+    /// doesn't parse.
+    pub fn load() -> Self {
+        let path = Self::path();
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                info!("no settings at {}, using defaults", path.display());
+                return Self::default();
+            }
+            Err(e) => {
+                warn!("failed to read {}: {e}", path.display());
+                return Self::default();
+            }
+        };
+
+        let (settings, errors) = Self::parse_config(&contents);
+        for (line, e) in errors {
+            warn!("{}:{line}: {e}", path.display());
+        }
+
+        settings
+    }
+
+    /// Parses the contents of a settings file, returning the bad lines by line number
+    /// alongside everything that did parse.
+    fn parse_config(contents: &str) -> (Self, Vec<(usize, String)>) {
+        let mut settings = Self::default();
+        let mut errors = vec![];
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let result = match line.split_once('=') {
+                Some((key, value)) => settings.set(key.trim(), value.trim()),
+                None => Err("expected key = value".to_owned()),
+            };
+
+            if let Err(e) = result {
+                errors.push((number + 1, e));
+            }
+        }
+
+        (settings, errors)
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "mouse.sensitivity" => self.mouse.sensitivity = parse(value)?,
+            "mouse.invert_y" => self.mouse.invert_y = parse(value)?,
+            "mouse.smoothing" => self.mouse.smoothing = parse(value)?,
+            "mouse.acceleration" => self.mouse.acceleration = parse(value)?,
+            "graphics.frames_in_flight" => self.graphics.frames_in_flight = parse(value)?,
+            "graphics.low_latency" => self.graphics.low_latency = parse(value)?,
+            "graphics.hdr" => self.graphics.hdr = parse(value)?,
+            "graphics.window_size" => {
+                self.graphics.window_size = match value.split_once('x') {
+                    Some((width, height)) => (parse(width.trim())?, parse(height.trim())?),
+                    None => return Err(format!("invalid window size {value:?}")),
+                }
+            }
+            "graphics.window_mode" => self.graphics.window_mode = parse_window_mode(value)?,
+            "frame_rate_limit" => {
+                self.frame_rate_limit = match value {
+                    "none" => None,
+                    _ => match parse::<f32>(value)? {
+                        fps if fps.is_finite() && fps > 0.0 => Some(fps),
+                        _ => {
+                            return Err(format!("frame rate limit must be positive, got {value:?}"))
+                        }
+                    },
+                }
+            }
+            "metrics" => self.metrics = parse(value)?,
+            _ => return Err(format!("unknown setting {key:?}")),
+        }
+
+        Ok(())
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        fs::create_dir_all(config_dir())?;
+        fs::write(Self::path(), self.to_config())
+    }
+
+    fn to_config(&self) -> String {
+        format!(
+            concat!(
+                "mouse.sensitivity = {}\n",
+                "mouse.invert_y = {}\n",
+                "mouse.smoothing = {}\n",
+                "mouse.acceleration = {}\n",
+                "graphics.frames_in_flight = {}\n",
+                "graphics.low_latency = {}\n",
+                "graphics.hdr = {}\n",
+                "graphics.window_size = {}x{}\n",
+                "graphics.window_mode = {}\n",
+                "frame_rate_limit = {}\n",
+                "metrics = {}\n",
+            ),
+            self.mouse.sensitivity,
+            self.mouse.invert_y,
+            self.mouse.smoothing,
+            self.mouse.acceleration,
+            self.graphics.frames_in_flight,
+            self.graphics.low_latency,
+            self.graphics.hdr,
+            self.graphics.window_size.0,
+            self.graphics.window_size.1,
+            format_window_mode(self.graphics.window_mode),
+            self.frame_rate_limit
+                .map_or("none".to_owned(), |limit| limit.to_string()),
+            self.metrics,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut settings = Settings::default();
+        settings.mouse.sensitivity = 0.01;
+        settings.mouse.invert_y = true;
+        settings.mouse.smoothing = 0.05;
+        settings.graphics.frames_in_flight = 3;
+        settings.graphics.hdr = true;
+        settings.graphics.window_size = (1280, 720);
+        settings.graphics.window_mode = WindowMode::Fullscreen {
+            display: 1,
+            width: 2560,
+            height: 1440,
+        };
+        settings.frame_rate_limit = Some(144.0);
+        settings.metrics = true;
+
+        let (parsed, errors) = Settings::parse_config(&settings.to_config());
+
+        assert_eq!(errors, vec![]);
+        assert_eq!(parsed, settings);
+    }
+
+    #[test]
+    fn round_trip_defaults() {
+        let (parsed, errors) = Settings::parse_config(&Settings::default().to_config());
+
+        assert_eq!(errors, vec![]);
+        assert_eq!(parsed, Settings::default());
+    }
+
+    #[test]
+    fn window_modes() {
+        for mode in [
+            WindowMode::Windowed,
+            WindowMode::Borderless { display: 0 },
+            WindowMode::Fullscreen {
+                display: 2,
+                width: 1920,
+                height: 1080,
+            },
+        ] {
+            assert_eq!(parse_window_mode(&format_window_mode(mode)), Ok(mode));
+        }
+
+        assert_eq!(
+            parse_window_mode("  borderless   1 "),
+            Ok(WindowMode::Borderless { display: 1 })
+        );
+    }
+
+    #[test]
+    fn bad_window_modes() {
+        for value in [
+            "",
+            "maximised",
+            "borderless",
+            "borderless one",
+            "fullscreen 0 1920",
+            "fullscreen 0 1920 -1080",
+            "windowed 0",
+        ] {
+            assert!(parse_window_mode(value).is_err(), "{value:?} parsed");
+        }
+    }
+
+    #[test]
+    fn bad_values_keep_defaults() {
+        let contents = concat!(
+            "# comment\n",
+            "\n",
+            "mouse.sensitivity = fast\n",
+            "graphics.hdr = yes\n",
+            "graphics.window_size = 1280\n",
+            "graphics.window_mode = fullscreen 0\n",
+            "frame_rate_limit = 0\n",
+            "frame_rate_limit = -60\n",
+            "frame_rate_limit = NaN\n",
+            "frame_rate_limit = inf\n",
+            "no equals sign\n",
+            "unknown.key = 1\n",
+            "metrics = true\n",
+        );
+
+        let (parsed, errors) = Settings::parse_config(contents);
+        let lines: Vec<_> = errors.iter().map(|(line, _)| *line).collect();
+
+        assert_eq!(lines, (3..=12).collect::<Vec<_>>());
+        assert_eq!(
+            parsed,
+            Settings {
+                metrics: true,
+                ..Settings::default()
+            }
+        );
+    }
+
+    #[test]
+    fn frame_rate_limit() {
+        let (parsed, _) = Settings::parse_config("frame_rate_limit = 60\n");
+        assert_eq!(parsed.frame_rate_limit, Some(60.0));
+
+        let (parsed, errors) = Settings::parse_config("frame_rate_limit = none\n");
+        assert_eq!(errors, vec![]);
+        assert_eq!(parsed.frame_rate_limit, None);
+    }
+}
@@ -2,11 +2,22 @@
 
 layout(location = 0) in vec3 fragColor;
 layout(location = 1) in vec2 fragTexCoord;
+layout(location = 2) in vec3 fragWorldNormal;
 
 layout(location = 0) out vec4 outColor;
 
 layout(binding = 1) uniform sampler2D texSampler;
 
+// Matches DebugView in pipeline.rs
+layout(constant_id = 4) const uint debug_view = 0u;
+const uint DEBUG_VIEW_ALBEDO = 1u;
+const uint DEBUG_VIEW_NORMALS = 2u;
+const uint DEBUG_VIEW_DEPTH = 3u;
+const uint DEBUG_VIEW_OVERDRAW = 4u;
+
+// Matches output_scale in pipeline.rs, above 1.0 for HDR output
+layout(constant_id = 5) const float output_scale = 1.0;
+
 layout( push_constant ) uniform constants
 {
     layout(offset = 32) vec3 sun_direction;
@@ -23,9 +34,23 @@ void main() {
 
 
     vec3 tex = texture(texSampler, vec2(fragTexCoord.x, -fragTexCoord.y)).xyz;
-    outColor = vec4(tex * (
-                    lighting(sun, sun_color) +
-                    0.01 * vec3(0.5, 0.7, 0.9) +
-                    0.2 * lighting(vec3(-sun.x,sun.y,-sun.z), vec3(0.5, 0.7, 0.9))
-                ), 1.0);
+
+    if (debug_view == DEBUG_VIEW_ALBEDO) {
+        outColor = vec4(tex, 1.0);
+    } else if (debug_view == DEBUG_VIEW_NORMALS) {
+        outColor = vec4(normalize(fragWorldNormal) * 0.5 + 0.5, 1.0);
+    } else if (debug_view == DEBUG_VIEW_DEPTH) {
+        // Depth is already linear in view distance with this projection
+        outColor = vec4(vec3(1.0 - gl_FragCoord.z), 1.0);
+    } else if (debug_view == DEBUG_VIEW_OVERDRAW) {
+        outColor = vec4(0.1, 0.04, 0.01, 1.0);
+    } else {
+        outColor = vec4(tex * (
+                        lighting(sun, sun_color) +
+                        0.01 * vec3(0.5, 0.7, 0.9) +
+                        0.2 * lighting(vec3(-sun.x,sun.y,-sun.z), vec3(0.5, 0.7, 0.9))
+                    ), 1.0);
+    }
+
+    outColor.rgb *= output_scale;
 }
@@ -6,6 +6,7 @@ layout(location = 2) in vec2 inTexCoord;
 
 layout(location = 0) out vec3 fragColor;
 layout(location = 1) out vec2 fragTexCoord;
+layout(location = 2) out vec3 fragWorldNormal;
 
 layout(constant_id = 0) const float scale_x = 0.0;
 layout(constant_id = 1) const float scale_y = 0.0;
@@ -67,4 +68,5 @@ void main() {
 
     fragColor = inColor;
     fragTexCoord = inTexCoord;
+    fragWorldNormal = rotate(inColor, model_rotor);
 }
@@ -0,0 +1,16 @@
+#version 450
+
+layout(location = 0) in vec2 fragTexCoord;
+layout(location = 1) in vec4 fragColor;
+
+layout(location = 0) out vec4 outColor;
+
+layout(binding = 1) uniform sampler2D texSampler;
+
+// Matches output_scale in pipeline.rs, above 1.0 for HDR output
+layout(constant_id = 5) const float output_scale = 1.0;
+
+void main() {
+    outColor = texture(texSampler, fragTexCoord) * fragColor;
+    outColor.rgb *= output_scale;
+}
@@ -0,0 +1,66 @@
+#version 450
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec2 inSize;
+layout(location = 2) in vec4 inUvRect;
+layout(location = 3) in vec4 inColor;
+
+layout(location = 0) out vec2 fragTexCoord;
+layout(location = 1) out vec4 fragColor;
+
+layout(constant_id = 0) const float scale_x = 0.0;
+layout(constant_id = 1) const float scale_y = 0.0;
+layout(constant_id = 2) const float front_clip = 0.0;
+layout(constant_id = 3) const float back_clip = 0.0;
+
+layout(binding = 0) uniform View {
+    float x;
+    float y;
+    float z;
+    float rx;
+    float ry;
+    float rz;
+    float rw;
+} view;
+
+const vec2 corners[6] = vec2[](
+    vec2(-0.5, -0.5), vec2( 0.5, -0.5), vec2( 0.5,  0.5),
+    vec2(-0.5, -0.5), vec2( 0.5,  0.5), vec2(-0.5,  0.5)
+);
+
+vec3 rotate(vec3 vec, vec4 rotor) {
+    float x = rotor.x;
+    float y = rotor.y;
+    float z = rotor.z;
+    float w = rotor.w;
+
+    vec4 q = vec4(
+        dot(vec, vec3( x, y, z)),
+        dot(vec, vec3(-y, x, w)),
+        dot(vec, vec3(-z,-w, x)),
+        dot(vec, vec3( w,-z, y))
+    );
+
+    return vec3(
+        dot(q, vec4( x, y, z, w)),
+        dot(q, vec4(-y, x, w,-z)),
+        dot(q, vec4(-z,-w, x, y))
+    );
+}
+
+void main() {
+    vec3 camera_position = vec3(view.x, view.y, view.z);
+    vec4 camera_rotor = vec4(view.rx, view.ry, view.rz, view.rw);
+
+    vec2 corner = corners[gl_VertexIndex];
+
+    // Expanding the quad after the view rotation keeps it facing the camera
+    vec3 rotated = rotate(inPosition - camera_position, camera_rotor) + vec3(corner * inSize, 0.0);
+
+    gl_Position = vec4(front_clip*vec2(scale_x, scale_y)*rotated.xy/rotated.z, (-rotated.z - front_clip)/back_clip, 1.0);
+
+    // View +x and +y both end up towards the top left of the screen after the divide by a
+    // negative z, which is where the top left of the uv rect goes
+    fragTexCoord = mix(inUvRect.xy, inUvRect.zw, 0.5 - corner);
+    fragColor = inColor;
+}
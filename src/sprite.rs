@@ -0,0 +1,428 @@
+use std::{mem::offset_of, ops::Deref, rc::Rc};
+
+use ash::vk;
+use log::{info, warn};
+use ultraviolet::{Isometry3, Vec2, Vec3, Vec4};
+
+use crate::{
+    buffer::MappedBuffer,
+    descriptors::DescriptorSetLayout,
+    device::Device,
+    image::Image,
+    pipeline::{camera_parameters, CAMERA_SPECIALIZATION_MAP_ENTRIES},
+    render_pass::RenderPass,
+    renderer::{Renderer, UniformBufferObject},
+    resource_tracker::{self, ResourceKind},
+    sampler::Sampler,
+    shader_module::spv,
+};
+
+/// Sprites drawn per frame, anything past this is dropped with a warning.
+const MAX_SPRITES: usize = 4096;
+
+/// A camera facing quad, passed to the sprite pipeline as per-instance vertex data.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct Sprite {
+    pub position: Vec3,
+    pub size: Vec2,
+    /// Texture coordinates of the top left and bottom right corners.
+    pub uv_rect: Vec4,
+    pub color: Vec4,
+}
+
+impl Default for Sprite {
+    fn default() -> Self {
+        Self {
+            position: Vec3::zero(),
+            size: Vec2::one(),
+            uv_rect: Vec4::new(0.0, 0.0, 1.0, 1.0),
+            color: Vec4::one(),
+        }
+    }
+}
+
+impl Sprite {
+    const fn get_binding_description() -> vk::VertexInputBindingDescription {
+        vk::VertexInputBindingDescription {
+            binding: 0,
+            stride: size_of::<Sprite>() as u32,
+            input_rate: vk::VertexInputRate::INSTANCE,
+        }
+    }
+
+    const fn get_attribute_descriptions() -> [vk::VertexInputAttributeDescription; 4] {
+        [
+            vk::VertexInputAttributeDescription {
+                location: 0,
+                binding: 0,
+                format: vk::Format::R32G32B32_SFLOAT,
+                offset: offset_of!(Self, position) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                location: 1,
+                binding: 0,
+                format: vk::Format::R32G32_SFLOAT,
+                offset: offset_of!(Self, size) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                location: 2,
+                binding: 0,
+                format: vk::Format::R32G32B32A32_SFLOAT,
+                offset: offset_of!(Self, uv_rect) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                location: 3,
+                binding: 0,
+                format: vk::Format::R32G32B32A32_SFLOAT,
+                offset: offset_of!(Self, color) as u32,
+            },
+        ]
+    }
+}
+
+/// Alpha blended, depth tested but not depth writing, so it's drawn after the opaque meshes
+/// in the same render pass.
+pub struct SpritePipeline {
+    pub pipeline: vk::Pipeline,
+    pub pipeline_layout: vk::PipelineLayout,
+
+    device: Rc<ash::Device>,
+}
+
+impl SpritePipeline {
+    /// `render_pass` is the main pipeline's, which the sprites are drawn in.
+    pub fn new(
+        device: &Device,
+        extent: &vk::Extent2D,
+        render_pass: &RenderPass,
+        descriptor_set_layout: &DescriptorSetLayout,
+        output_scale: f32,
+    ) -> Self {
+        let vert_shader_module = spv!(device.device.clone(), "sprite.vert");
+        let frag_shader_module = spv!(device.device.clone(), "sprite.frag");
+
+        let camera_parameters = camera_parameters(extent);
+
+        let specialization_info = vk::SpecializationInfo::default()
+            .map_entries(&CAMERA_SPECIALIZATION_MAP_ENTRIES)
+            .data(unsafe {
+                std::slice::from_raw_parts(
+                    &camera_parameters as *const Vec4 as *const u8,
+                    std::mem::size_of::<Vec4>(),
+                )
+            });
+
+        let fragment_specialization_map_entries = [vk::SpecializationMapEntry {
+            constant_id: 5,
+            offset: 0,
+            size: std::mem::size_of::<f32>(),
+        }];
+
+        let fragment_specialization_info = vk::SpecializationInfo::default()
+            .map_entries(&fragment_specialization_map_entries)
+            .data(unsafe {
+                std::slice::from_raw_parts(
+                    &output_scale as *const f32 as *const u8,
+                    std::mem::size_of::<f32>(),
+                )
+            });
+
+        let shader_stages = [
+            vk::PipelineShaderStageCreateInfo::default()
+                .stage(vk::ShaderStageFlags::VERTEX)
+                .module(*vert_shader_module)
+                .name(c"main")
+                .specialization_info(&specialization_info),
+            vk::PipelineShaderStageCreateInfo::default()
+                .stage(vk::ShaderStageFlags::FRAGMENT)
+                .module(*frag_shader_module)
+                .name(c"main")
+                .specialization_info(&fragment_specialization_info),
+        ];
+
+        let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+        let dynamic_state =
+            vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
+
+        let vertex_binding_descriptions = [Sprite::get_binding_description()];
+        let vertex_attribute_descriptions = Sprite::get_attribute_descriptions();
+        let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::default()
+            .vertex_binding_descriptions(&vertex_binding_descriptions)
+            .vertex_attribute_descriptions(&vertex_attribute_descriptions);
+
+        let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::default()
+            .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+            .primitive_restart_enable(false);
+
+        let viewport_state = vk::PipelineViewportStateCreateInfo::default()
+            .viewport_count(1)
+            .scissor_count(1);
+
+        let rasterizer = vk::PipelineRasterizationStateCreateInfo::default()
+            .depth_clamp_enable(false)
+            .rasterizer_discard_enable(false)
+            .polygon_mode(vk::PolygonMode::FILL)
+            .line_width(1.0)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .depth_bias_enable(false);
+
+        let multisampling = vk::PipelineMultisampleStateCreateInfo::default()
+            .sample_shading_enable(false)
+            .rasterization_samples(device.mssa_samples)
+            .min_sample_shading(1.0);
+
+        let depth_stencil = vk::PipelineDepthStencilStateCreateInfo::default()
+            .depth_test_enable(true)
+            .depth_write_enable(false)
+            .depth_compare_op(vk::CompareOp::LESS)
+            .depth_bounds_test_enable(false)
+            .stencil_test_enable(false);
+
+        let color_blend_attachment = [vk::PipelineColorBlendAttachmentState {
+            blend_enable: vk::TRUE,
+            src_color_blend_factor: vk::BlendFactor::SRC_ALPHA,
+            dst_color_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+            color_blend_op: vk::BlendOp::ADD,
+            src_alpha_blend_factor: vk::BlendFactor::ONE,
+            dst_alpha_blend_factor: vk::BlendFactor::ZERO,
+            alpha_blend_op: vk::BlendOp::ADD,
+            color_write_mask: vk::ColorComponentFlags::RGBA,
+        }];
+
+        let color_blending = vk::PipelineColorBlendStateCreateInfo::default()
+            .logic_op_enable(false)
+            .logic_op(vk::LogicOp::COPY)
+            .attachments(&color_blend_attachment);
+
+        let descriptor_set_layouts = [descriptor_set_layout.layout];
+
+        let pipeline_layout_info =
+            vk::PipelineLayoutCreateInfo::default().set_layouts(&descriptor_set_layouts);
+
+        let pipeline_layout = unsafe {
+            device
+                .create_pipeline_layout(&pipeline_layout_info, None)
+                .unwrap()
+        };
+
+        let pipeline_info = [vk::GraphicsPipelineCreateInfo::default()
+            .stages(&shader_stages)
+            .vertex_input_state(&vertex_input_info)
+            .input_assembly_state(&input_assembly)
+            .viewport_state(&viewport_state)
+            .rasterization_state(&rasterizer)
+            .multisample_state(&multisampling)
+            .depth_stencil_state(&depth_stencil)
+            .color_blend_state(&color_blending)
+            .dynamic_state(&dynamic_state)
+            .layout(pipeline_layout)
+            .render_pass(**render_pass)
+            .subpass(0)];
+
+        let pipeline = unsafe {
+            device
+                .create_graphics_pipelines(vk::PipelineCache::null(), &pipeline_info, None)
+                .expect("failed to create sprite pipeline!")[0]
+        };
+
+        Self {
+            device: device.device.clone(),
+            pipeline,
+            pipeline_layout,
+        }
+    }
+}
+
+impl Deref for SpritePipeline {
+    type Target = vk::Pipeline;
+
+    fn deref(&self) -> &Self::Target {
+        &self.pipeline
+    }
+}
+
+impl Drop for SpritePipeline {
+    fn drop(&mut self) {
+        info!("dropped sprite pipeline");
+        unsafe {
+            self.device.destroy_pipeline(self.pipeline, None);
+            self.device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SpriteTexture(usize);
+
+/// Collects sprites over a frame and draws them back to front, with one instanced draw call
+/// per run of consecutive sprites sharing a texture.
+pub struct SpriteBatch {
+    instance_buffers: Vec<MappedBuffer<Sprite>>,
+    /// Indexed by texture, then by frame in flight
+    descriptor_sets: Vec<Vec<vk::DescriptorSet>>,
+    sprites: Vec<(SpriteTexture, Sprite)>,
+}
+
+impl SpriteBatch {
+    pub fn new(gfx: &Renderer) -> Self {
+        let instance_buffers = (0..gfx.frames_in_flight)
+            .map(|_| {
+                MappedBuffer::new(
+                    gfx.device.device.clone(),
+                    &gfx.instance,
+                    gfx.device.physical_device,
+                    &vec![Sprite::default(); MAX_SPRITES],
+                    vk::BufferUsageFlags::VERTEX_BUFFER,
+                    vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+                )
+            })
+            .collect();
+
+        Self {
+            instance_buffers,
+            descriptor_sets: vec![],
+            sprites: vec![],
+        }
+    }
+
+    /// Allocates descriptor sets pairing `image` with each frame's uniform buffer.
+    pub fn add_texture(
+        &mut self,
+        gfx: &mut Renderer,
+        image: &Image,
+        sampler: &Sampler,
+    ) -> SpriteTexture {
+        let descriptor_sets = gfx
+            .main_window
+            .descriptor_pool
+            .create_descriptor_sets(gfx.frames_in_flight.try_into().unwrap());
+
+        for (uniform_buffer, &descriptor_set) in
+            gfx.main_window.uniform_buffers.iter().zip(&descriptor_sets)
+        {
+            let buffer_info = [vk::DescriptorBufferInfo::default()
+                .buffer(*uniform_buffer.buffer)
+                .offset(0)
+                .range(size_of::<UniformBufferObject>().try_into().unwrap())];
+
+            let image_info = [vk::DescriptorImageInfo::default()
+                .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                .image_view(image.view)
+                .sampler(sampler.sampler)];
+
+            let descriptor_writes = [
+                vk::WriteDescriptorSet::default()
+                    .dst_set(descriptor_set)
+                    .dst_binding(0)
+                    .dst_array_element(0)
+                    .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                    .descriptor_count(1)
+                    .buffer_info(&buffer_info),
+                vk::WriteDescriptorSet::default()
+                    .dst_set(descriptor_set)
+                    .dst_binding(1)
+                    .dst_array_element(0)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .descriptor_count(1)
+                    .image_info(&image_info),
+            ];
+
+            unsafe { gfx.device.update_descriptor_sets(&descriptor_writes, &[]) };
+        }
+        resource_tracker::mark_used(ResourceKind::Image, image.image);
+        resource_tracker::mark_used(ResourceKind::Sampler, sampler.sampler);
+
+        self.descriptor_sets.push(descriptor_sets);
+
+        SpriteTexture(self.descriptor_sets.len() - 1)
+    }
+
+    /// Queues a sprite for the next [`record`](Self::record), until [`clear`](Self::clear).
+    pub fn draw(&mut self, texture: SpriteTexture, sprite: Sprite) {
+        self.sprites.push((texture, sprite));
+    }
+
+    /// Drops anything past [`MAX_SPRITES`] and sorts the rest furthest from the camera first,
+    /// since alpha blending needs them drawn back to front.
+    fn sort_back_to_front(&mut self, camera_transform: Isometry3) {
+        if self.sprites.len() > MAX_SPRITES {
+            warn!(
+                "{} sprites queued, only drawing the first {MAX_SPRITES}",
+                self.sprites.len()
+            );
+            self.sprites.truncate(MAX_SPRITES);
+        }
+
+        // Visible sprites have a negative view z, so the furthest is the most negative
+        let view_z = |sprite: &Sprite| {
+            (sprite.position - camera_transform.translation)
+                .rotated_by(camera_transform.rotation)
+                .z
+        };
+
+        self.sprites
+            .sort_by(|(_, a), (_, b)| view_z(a).total_cmp(&view_z(b)));
+    }
+
+    /// Instanced draws the queued sprites will take from `camera_transform`, one per run of
+    /// consecutive sprites sharing a texture once sorted.
+    pub fn draw_calls(&mut self, camera_transform: Isometry3) -> usize {
+        self.sort_back_to_front(camera_transform);
+
+        self.sprites.chunk_by(|(a, _), (b, _)| a == b).count()
+    }
+
+    /// Draws the queued sprites. Must be called inside the main render pass, while `frame`'s
+    /// fence is signalled, since it writes that frame's instance buffer.
+    pub fn record(
+        &mut self,
+        device: &Device,
+        pipeline: &SpritePipeline,
+        cmd_buf: vk::CommandBuffer,
+        frame: usize,
+        camera_transform: Isometry3,
+    ) {
+        self.sort_back_to_front(camera_transform);
+
+        if self.sprites.is_empty() {
+            return;
+        }
+
+        let instance_buffer = &mut self.instance_buffers[frame];
+        resource_tracker::mark_used(ResourceKind::Buffer, instance_buffer.buffer.buffer);
+        for (instance, (_, sprite)) in instance_buffer.mapped_memory.iter_mut().zip(&self.sprites) {
+            *instance = *sprite;
+        }
+
+        unsafe {
+            device.cmd_bind_pipeline(cmd_buf, vk::PipelineBindPoint::GRAPHICS, **pipeline);
+            device.cmd_bind_vertex_buffers(cmd_buf, 0, &[*instance_buffer.buffer], &[0]);
+
+            let mut first_instance = 0;
+            for batch in self.sprites.chunk_by(|(a, _), (b, _)| a == b) {
+                let SpriteTexture(texture) = batch[0].0;
+
+                device.cmd_bind_descriptor_sets(
+                    cmd_buf,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    pipeline.pipeline_layout,
+                    0,
+                    &[self.descriptor_sets[texture][frame]],
+                    &[],
+                );
+
+                let instance_count = batch.len() as u32;
+                device.cmd_draw(cmd_buf, 6, instance_count, 0, first_instance);
+                first_instance += instance_count;
+            }
+        }
+    }
+
+    /// Empties the queue. Called once per frame whether or not it was recorded, since a frame
+    /// skipped for an out of date swapchain never reaches [`record`](Self::record).
+    pub fn clear(&mut self) {
+        self.sprites.clear();
+    }
+}
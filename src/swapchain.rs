@@ -1,26 +1,40 @@
 use std::{mem::ManuallyDrop, ops::Deref};
 
 use ash::{khr, vk};
-use log::info;
+use log::{info, warn};
 
 use crate::{
     descriptors::DescriptorSetLayout,
     device::Device,
     image::{find_supported_format, Image, SwapchainImage},
-    pipeline::Pipeline,
+    pipeline::{output_scale, DebugView, Pipeline},
+    resource_tracker::{self, ResourceKind},
+    sprite::SpritePipeline,
 };
 
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SwapchainPreferences {
+    pub hdr: bool,
+    /// Prefer MAILBOX presentation, which doesn't block on vsync.
+    pub low_latency: bool,
+    /// Output mode of the main pipeline, which is rebuilt along with the swapchain.
+    pub debug_view: DebugView,
+}
+
 pub struct Swapchain {
     pub loader: khr::swapchain::Device,
     pub swapchain: vk::SwapchainKHR,
     pub pipeline: Pipeline,
+    pub sprite_pipeline: SpritePipeline,
     pub images: Vec<SwapchainImage>,
     pub depth_image: ManuallyDrop<Image>,
     pub color_image: Option<Image>,
     pub extent: vk::Extent2D,
+    pub surface_format: vk::SurfaceFormatKHR,
 }
 
 impl Swapchain {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         instance: &ash::Instance,
         device: &Device,
@@ -28,20 +42,25 @@ impl Swapchain {
         surface: vk::SurfaceKHR,
         extent: vk::Extent2D,
         descriptor_set_layout: &DescriptorSetLayout,
+        preferences: SwapchainPreferences,
         old_swapchain: Option<&Self>,
-    ) -> Self {
+    ) -> Result<Self, String> {
         let swapchain_loader = match old_swapchain {
             Some(swapchain) => swapchain.loader.clone(),
             None => khr::swapchain::Device::new(instance, device),
         };
 
-        let surface_format =
-            Self::choose_swap_surface_format(device.physical_device, surface_loader, surface);
+        let surface_format = Self::choose_swap_surface_format(
+            device.physical_device,
+            surface_loader,
+            surface,
+            preferences.hdr,
+        )?;
 
         let surface_capabilities = unsafe {
             surface_loader
                 .get_physical_device_surface_capabilities(device.physical_device, surface)
-                .unwrap()
+                .map_err(|e| format!("couldn't query surface capabilities: {e}"))?
         };
 
         let mut desired_image_count = surface_capabilities.min_image_count + 1;
@@ -68,13 +87,31 @@ impl Swapchain {
         let present_modes = unsafe {
             surface_loader
                 .get_physical_device_surface_present_modes(device.physical_device, surface)
-                .unwrap()
+                .map_err(|e| format!("couldn't query present modes: {e}"))?
         };
-        let present_mode = present_modes
-            .iter()
-            .cloned()
-            .find(|&mode| mode == vk::PresentModeKHR::FIFO_RELAXED)
+        let preferred_present_modes = if preferences.low_latency {
+            vec![
+                vk::PresentModeKHR::MAILBOX,
+                vk::PresentModeKHR::FIFO_RELAXED,
+            ]
+        } else {
+            vec![vk::PresentModeKHR::FIFO_RELAXED]
+        };
+        let present_mode = preferred_present_modes
+            .into_iter()
+            .find(|mode| present_modes.contains(mode))
             .unwrap_or(vk::PresentModeKHR::FIFO);
+        info!("present mode: {present_mode:?}");
+
+        // Concurrent sharing saves ownership transfers between the graphics and present queues
+        // on devices where they're separate families
+        let queue_family_indices = [device.graphics_index, device.present_index];
+        let (sharing_mode, queue_family_indices) = if device.graphics_index == device.present_index
+        {
+            (vk::SharingMode::EXCLUSIVE, &queue_family_indices[..0])
+        } else {
+            (vk::SharingMode::CONCURRENT, &queue_family_indices[..])
+        };
 
         let extent = vk::Extent2D { width, height };
         let swapchain_create_info = vk::SwapchainCreateInfoKHR::default()
@@ -84,7 +121,8 @@ impl Swapchain {
             .image_format(surface_format.format)
             .image_extent(extent)
             .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
-            .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .image_sharing_mode(sharing_mode)
+            .queue_family_indices(queue_family_indices)
             .pre_transform(pre_transform)
             .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
             .present_mode(present_mode)
@@ -98,7 +136,15 @@ impl Swapchain {
         let swapchain = unsafe {
             swapchain_loader
                 .create_swapchain(&swapchain_create_info, None)
-                .unwrap()
+                .map_err(|e| format!("couldn't create swapchain: {e}"))?
+        };
+
+        let swapchain_images = match unsafe { swapchain_loader.get_swapchain_images(swapchain) } {
+            Ok(images) => images,
+            Err(e) => {
+                unsafe { swapchain_loader.destroy_swapchain(swapchain, None) };
+                return Err(format!("couldn't get swapchain images: {e}"));
+            }
         };
 
         let depth_image = {
@@ -124,6 +170,8 @@ impl Swapchain {
             ))
         };
 
+        resource_tracker::mark_used(ResourceKind::Image, depth_image.image);
+
         let color_image = match device.mssa_samples {
             vk::SampleCountFlags::TYPE_1 => None,
             _ => Some(Image::new(
@@ -140,15 +188,31 @@ impl Swapchain {
             )),
         };
 
+        if let Some(color_image) = &color_image {
+            resource_tracker::mark_used(ResourceKind::Image, color_image.image);
+        }
+
+        let output_scale = output_scale(surface_format.color_space);
+
         let pipeline = Pipeline::new(
             instance,
             device,
             &extent,
             surface_format.format,
             descriptor_set_layout,
+            preferences.debug_view,
+            output_scale,
+        );
+
+        let sprite_pipeline = SpritePipeline::new(
+            device,
+            &extent,
+            &pipeline.render_pass,
+            descriptor_set_layout,
+            output_scale,
         );
 
-        let images = unsafe { swapchain_loader.get_swapchain_images(swapchain).unwrap() }
+        let images = swapchain_images
             .iter()
             .map(|&image| {
                 SwapchainImage::new(
@@ -163,39 +227,64 @@ impl Swapchain {
             })
             .collect::<Vec<_>>();
 
-        Self {
+        Ok(Self {
             loader: swapchain_loader,
             swapchain,
             pipeline,
+            sprite_pipeline,
             images,
             depth_image,
             color_image,
             extent,
-        }
+            surface_format,
+        })
     }
 
     fn choose_swap_surface_format(
         physical_device: vk::PhysicalDevice,
         surface_loader: &khr::surface::Instance,
         surface: vk::SurfaceKHR,
-    ) -> vk::SurfaceFormatKHR {
+        hdr: bool,
+    ) -> Result<vk::SurfaceFormatKHR, String> {
         let avaliable_formats = unsafe {
             surface_loader
                 .get_physical_device_surface_formats(physical_device, surface)
-                .unwrap()
+                .map_err(|e| format!("couldn't query surface formats: {e}"))?
+        };
+
+        let sdr_format = vk::SurfaceFormatKHR {
+            format: vk::Format::B8G8R8A8_SRGB,
+            color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+        };
+
+        // scRGB takes the same linear values the shaders already write, just without
+        // clamping to 1.0, so it only needs the paper white scale from output_scale.
+        let hdr_format = vk::SurfaceFormatKHR {
+            format: vk::Format::R16G16B16A16_SFLOAT,
+            color_space: vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT,
         };
 
-        avaliable_formats
+        let preferred_formats = if hdr {
+            vec![hdr_format, sdr_format]
+        } else {
+            vec![sdr_format]
+        };
+
+        // Any format the surface offers beats not starting at all
+        let surface_format = preferred_formats
             .iter()
-            .find_map(|&available_format| {
-                (available_format
-                    == (vk::SurfaceFormatKHR {
-                        format: vk::Format::B8G8R8A8_SRGB,
-                        color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
-                    }))
-                .then_some(available_format)
-            })
-            .unwrap_or(avaliable_formats[0])
+            .copied()
+            .find(|format| avaliable_formats.contains(format))
+            .or(avaliable_formats.first().copied())
+            .ok_or("surface offers no formats")?;
+
+        if hdr && surface_format != hdr_format {
+            warn!("HDR requested but the surface doesn't support scRGB output");
+        }
+
+        info!("surface format: {surface_format:?}");
+
+        Ok(surface_format)
     }
 }
 
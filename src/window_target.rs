@@ -0,0 +1,395 @@
+use std::rc::Rc;
+
+use ash::vk;
+use log::{info, warn};
+use sdl2::{
+    pixels::PixelFormatEnum,
+    sys::SDL_Vulkan_GetDrawableSize,
+    video::{DisplayMode, FullscreenType, WindowPos},
+};
+
+use crate::{
+    buffer::MappedBuffer,
+    command_buffer::{ActiveMultipleSubmitCommandBuffer, CommandPool, MultipleSubmitCommandBuffer},
+    descriptors::{DescriptorPool, DescriptorSetLayout},
+    device::Device,
+    image::SwapchainImage,
+    renderer::UniformBufferObject,
+    surface::Surface,
+    swapchain::{Swapchain, SwapchainPreferences},
+    synchronization::{Fence, Semaphore},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WindowMode {
+    Windowed,
+    Borderless {
+        display: i32,
+    },
+    Fullscreen {
+        display: i32,
+        width: u32,
+        height: u32,
+    },
+}
+
+fn drawable_extent(window: &sdl2::video::Window) -> vk::Extent2D {
+    let mut width: std::ffi::c_int = 0;
+    let mut height: std::ffi::c_int = 0;
+
+    unsafe {
+        SDL_Vulkan_GetDrawableSize(
+            window.raw(),
+            (&mut width) as *mut std::ffi::c_int,
+            (&mut height) as *mut std::ffi::c_int,
+        )
+    };
+
+    vk::Extent2D {
+        width: width.try_into().unwrap(),
+        height: height.try_into().unwrap(),
+    }
+}
+
+/// An OS window together with everything needed to present to it.
+pub struct WindowTarget {
+    // WARNING: Cleanup order matters here
+    pub image_avaliable_semaphores: Vec<Semaphore>,
+    pub render_finished_semaphores: Vec<Semaphore>,
+    pub in_flight_fences: Vec<Fence>,
+
+    pub descriptor_pool: DescriptorPool,
+    pub descriptor_sets: Vec<vk::DescriptorSet>,
+    pub uniform_buffers: Vec<MappedBuffer<UniformBufferObject>>,
+
+    pub command_buffers: Vec<MultipleSubmitCommandBuffer>,
+
+    pub swapchain: Swapchain,
+
+    pub surface: Surface,
+
+    pub window: sdl2::video::Window,
+
+    pub frames_in_flight: usize,
+    pub current_frame: usize,
+    pub window_mode: WindowMode,
+    pub preferences: SwapchainPreferences,
+
+    device: Rc<ash::Device>,
+}
+
+impl WindowTarget {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        window: sdl2::video::Window,
+        surface: Surface,
+        instance: &ash::Instance,
+        device: &Device,
+        command_pool: &CommandPool,
+        descriptor_set_layout: &DescriptorSetLayout,
+        frames_in_flight: usize,
+        preferences: SwapchainPreferences,
+    ) -> Result<Self, String> {
+        let present_supported = unsafe {
+            surface
+                .loader
+                .get_physical_device_surface_support(
+                    device.physical_device,
+                    device.present_index,
+                    *surface,
+                )
+                .unwrap_or(false)
+        };
+        if !present_supported {
+            return Err("present queue family can't present to this window".to_owned());
+        }
+
+        let swapchain = Swapchain::new(
+            instance,
+            device,
+            &surface.loader,
+            *surface,
+            drawable_extent(&window),
+            descriptor_set_layout,
+            preferences,
+            None,
+        )?;
+
+        let mut image_avaliable_semaphores = Vec::with_capacity(frames_in_flight);
+        let mut render_finished_semaphores = Vec::with_capacity(frames_in_flight);
+        let mut in_flight_fences = Vec::with_capacity(frames_in_flight);
+
+        let mut command_buffers = Vec::new();
+        let mut uniform_buffers = Vec::new();
+
+        let mut descriptor_pool = DescriptorPool::new(
+            device.device.clone(),
+            descriptor_set_layout,
+            frames_in_flight.try_into().unwrap(),
+        );
+
+        let descriptor_sets =
+            descriptor_pool.create_descriptor_sets(frames_in_flight.try_into().unwrap());
+
+        for _ in 0..frames_in_flight {
+            command_buffers.push(command_pool.create_command_buffer());
+
+            image_avaliable_semaphores.push(Semaphore::new(device.device.clone()));
+            render_finished_semaphores.push(Semaphore::new(device.device.clone()));
+            in_flight_fences.push(Fence::new(device.device.clone()));
+
+            uniform_buffers.push(MappedBuffer::new(
+                device.device.clone(),
+                instance,
+                device.physical_device,
+                &[UniformBufferObject::default()],
+                vk::BufferUsageFlags::UNIFORM_BUFFER,
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            ));
+        }
+
+        Ok(Self {
+            image_avaliable_semaphores,
+            render_finished_semaphores,
+            in_flight_fences,
+            descriptor_pool,
+            descriptor_sets,
+            uniform_buffers,
+            command_buffers,
+            swapchain,
+            surface,
+            window,
+            frames_in_flight,
+            current_frame: 0,
+            window_mode: WindowMode::Windowed,
+            preferences,
+            device: device.device.clone(),
+        })
+    }
+
+    pub fn draw<
+        F: FnMut(
+            &Device,
+            &Swapchain,
+            ActiveMultipleSubmitCommandBuffer,
+            &vk::DescriptorSet,
+            &mut [UniformBufferObject],
+            &SwapchainImage,
+        ) -> ActiveMultipleSubmitCommandBuffer,
+    >(
+        &mut self,
+        instance: &ash::Instance,
+        device: &Device,
+        descriptor_set_layout: &DescriptorSetLayout,
+        mut record_command_buffer: F,
+        framebuffer_resized: bool,
+    ) -> bool {
+        unsafe {
+            let fence = &[*self.in_flight_fences[self.current_frame]];
+            device.wait_for_fences(fence, true, u64::MAX).unwrap();
+
+            let (image_index, mut recreate_swapchain) = match (
+                self.swapchain.loader.acquire_next_image(
+                    *self.swapchain,
+                    u64::MAX,
+                    *self.image_avaliable_semaphores[self.current_frame],
+                    vk::Fence::null(),
+                ),
+                framebuffer_resized,
+            ) {
+                (Ok((image_index, true)), _) | (Ok((image_index, false)), true) => {
+                    (image_index, true)
+                }
+                (Ok((image_index, false)), false) => (image_index, false),
+                (Err(vk::Result::ERROR_OUT_OF_DATE_KHR), _) => {
+                    self.recreate_swapchain(instance, device, descriptor_set_layout);
+                    return false;
+                }
+                (Err(_), _) => {
+                    panic!("failed to acquire swapchain image")
+                }
+            };
+
+            device.reset_fences(fence).unwrap();
+
+            take_mut::take(
+                self.command_buffers.get_mut(self.current_frame).unwrap(),
+                |command_buffer| {
+                    command_buffer
+                        .begin()
+                        .record(|command_buffer| {
+                            record_command_buffer(
+                                device,
+                                &self.swapchain,
+                                command_buffer,
+                                &self.descriptor_sets[self.current_frame],
+                                self.uniform_buffers[self.current_frame].mapped_memory,
+                                &self.swapchain.images[image_index as usize],
+                            )
+                        })
+                        .end()
+                        .submit(
+                            device.graphics_queue,
+                            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                            *self.image_avaliable_semaphores[self.current_frame],
+                            *self.render_finished_semaphores[self.current_frame],
+                            *self.in_flight_fences[self.current_frame],
+                        )
+                },
+            );
+
+            let swapchains = [*self.swapchain];
+            let indices: [u32; 1] = [image_index];
+
+            let wait_semaphore = [*self.render_finished_semaphores[self.current_frame]];
+            let present_info = vk::PresentInfoKHR::default()
+                .wait_semaphores(&wait_semaphore)
+                .swapchains(&swapchains)
+                .image_indices(&indices);
+
+            match self
+                .swapchain
+                .loader
+                .queue_present(device.present_queue, &present_info)
+            {
+                Ok(true) | Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => recreate_swapchain = true,
+                Err(e) => panic!("{}", e),
+                _ => (),
+            };
+
+            if recreate_swapchain {
+                self.recreate_swapchain(instance, device, descriptor_set_layout);
+            }
+        }
+
+        self.current_frame = (self.current_frame + 1) % self.frames_in_flight;
+
+        false
+    }
+
+    pub fn set_window_mode(&mut self, mode: WindowMode) -> Result<(), String> {
+        let move_to_display = |window: &mut sdl2::video::Window, display: i32| {
+            let bounds = window.subsystem().display_bounds(display)?;
+            window.set_position(
+                WindowPos::Positioned(bounds.x()),
+                WindowPos::Positioned(bounds.y()),
+            );
+
+            Ok::<(), String>(())
+        };
+
+        match mode {
+            WindowMode::Windowed => self.window.set_fullscreen(FullscreenType::Off)?,
+            WindowMode::Borderless { display } => {
+                self.window.set_fullscreen(FullscreenType::Off)?;
+                move_to_display(&mut self.window, display)?;
+                self.window.set_fullscreen(FullscreenType::Desktop)?;
+            }
+            WindowMode::Fullscreen {
+                display,
+                width,
+                height,
+            } => {
+                let (Ok(w), Ok(h)) = (width.try_into(), height.try_into()) else {
+                    return Err(format!("invalid fullscreen resolution {width}x{height}"));
+                };
+
+                let display_mode = self.window.subsystem().closest_display_mode(
+                    display,
+                    &DisplayMode::new(PixelFormatEnum::Unknown, w, h, 0),
+                )?;
+
+                self.window.set_fullscreen(FullscreenType::Off)?;
+                move_to_display(&mut self.window, display)?;
+                self.window.set_display_mode(display_mode)?;
+                self.window.set_fullscreen(FullscreenType::True)?;
+            }
+        }
+
+        info!("window mode: {mode:?}");
+        self.window_mode = mode;
+
+        Ok(())
+    }
+
+    pub fn toggle_borderless(&mut self) -> Result<(), String> {
+        self.set_window_mode(match self.window_mode {
+            WindowMode::Borderless { .. } => WindowMode::Windowed,
+            _ => WindowMode::Borderless {
+                display: self.window.display_index()?,
+            },
+        })
+    }
+
+    pub fn toggle_fullscreen(&mut self) -> Result<(), String> {
+        self.set_window_mode(match self.window_mode {
+            WindowMode::Fullscreen { .. } => WindowMode::Windowed,
+            _ => {
+                let display = self.window.display_index()?;
+                let desktop_mode = self.window.subsystem().desktop_display_mode(display)?;
+
+                let (Ok(width), Ok(height)) =
+                    (desktop_mode.w.try_into(), desktop_mode.h.try_into())
+                else {
+                    return Err(format!(
+                        "invalid desktop resolution {}x{}",
+                        desktop_mode.w, desktop_mode.h
+                    ));
+                };
+
+                WindowMode::Fullscreen {
+                    display,
+                    width,
+                    height,
+                }
+            }
+        })
+    }
+
+    /// Switches between HDR and SDR output, recreating the swapchain with the new format.
+    pub fn set_hdr(
+        &mut self,
+        instance: &ash::Instance,
+        device: &Device,
+        descriptor_set_layout: &DescriptorSetLayout,
+        enabled: bool,
+    ) {
+        self.preferences.hdr = enabled;
+        self.recreate_swapchain(instance, device, descriptor_set_layout);
+    }
+
+    pub fn recreate_swapchain(
+        &mut self,
+        instance: &ash::Instance,
+        device: &Device,
+        descriptor_set_layout: &DescriptorSetLayout,
+    ) {
+        let extent = drawable_extent(&self.window);
+
+        unsafe { device.device_wait_idle().unwrap() };
+
+        let swapchain = Swapchain::new(
+            instance,
+            device,
+            &self.surface.loader,
+            *self.surface,
+            extent,
+            descriptor_set_layout,
+            self.preferences,
+            Some(&self.swapchain),
+        )
+        .expect("failed to recreate swapchain");
+
+        self.swapchain = swapchain;
+    }
+}
+
+impl Drop for WindowTarget {
+    fn drop(&mut self) {
+        // A secondary window can be dropped while the renderer keeps going, so its frames have
+        // to finish before their sync objects and swapchain are destroyed.
+        if let Err(e) = unsafe { self.device.device_wait_idle() } {
+            warn!("failed to wait for device idle before dropping window: {e}");
+        }
+    }
+}